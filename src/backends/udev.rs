@@ -10,7 +10,7 @@ use smithay::{
         drm::{
             self,
             compositor::{DrmCompositor, RenderFrameResult},
-            DrmAccessError, DrmDevice, DrmDeviceFd, DrmError, DrmNode, NodeType,
+            DrmAccessError, DrmDevice, DrmDeviceFd, DrmError, DrmEventTime, DrmNode, NodeType,
         },
         egl::{EGLDevice, EGLDisplay},
         libinput::{LibinputInputBackend, LibinputSessionInterface},
@@ -31,7 +31,9 @@ use smithay::{
         SwapBuffersError,
     },
     delegate_dmabuf,
-    desktop::{layer_map_for_output, space::SpaceElement, LayerSurface},
+    desktop::{
+        layer_map_for_output, space::SpaceElement, utils::OutputPresentationFeedback, LayerSurface,
+    },
     output::{Mode as WlMode, Output, PhysicalProperties},
     reexports::{
         calloop::{
@@ -39,18 +41,22 @@ use smithay::{
             EventLoop, RegistrationToken,
         },
         drm::{
-            control::{crtc, ModeTypeFlags},
+            control::{
+                atomic::AtomicModeReq, connector, crtc, property, AtomicCommitFlags,
+                Device as ControlDevice, Mode as DrmMode, ModeTypeFlags, ResourceHandle,
+            },
             Device as DrmDeviceTrait,
         },
         input::Libinput,
         rustix::fs::OFlags,
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
         wayland_server::{
             backend::GlobalId,
             protocol::{wl_output::WlOutput, wl_shm},
             Display, DisplayHandle,
         },
     },
-    utils::{DeviceFd, Point, Rectangle, Scale, Size, Transform},
+    utils::{DeviceFd, Logical, Physical, Point, Rectangle, Scale, Size, Transform},
     wayland::{
         dmabuf::{DmabufFeedbackBuilder, DmabufGlobal, DmabufHandler, DmabufState, ImportNotifier},
         shell::wlr_layer::Layer,
@@ -64,17 +70,32 @@ use smithay_drm_extras::{
 use tracing::{debug, error, info, trace, warn};
 
 use crate::{
+    config::{OutputConfig, Vrr},
+    delegate_gamma_control, delegate_output_management, delegate_output_power_management,
     delegate_screencopy_manager,
-    protocols::screencopy::{frame::Screencopy, ScreencopyHandler, ScreencopyManagerState},
+    handlers::presentation::refresh_interval,
+    protocols::{
+        gamma_control::{GammaControlHandler, GammaControlManagerState},
+        output_management::{
+            HeadConfiguration, ModeConfiguration, OutputManagementHandler, OutputManagementState,
+        },
+        output_power_management::{OutputPowerManagementHandler, OutputPowerManagementState},
+        screencopy::{frame::Screencopy, ScreencopyHandler, ScreencopyManagerState},
+    },
     state::{Backend, CalloopData, MagmaState, CONFIG},
     utils::{
-        process,
-        render::{border::BorderShader, init_shaders, CustomRenderElements},
+        modeline,
+        render::{
+            background::{background_texture, clear_color},
+            border::BorderShader,
+            cursor::{cursor_elements, CursorTextures},
+            dnd_icon_elements, init_shaders, lock_elements, output_size,
+            override_redirect_elements, CustomRenderElements, LOCKED_COLOR,
+        },
+        tiling::bsp_update_layout,
     },
 };
 
-static CURSOR_DATA: &[u8] = include_bytes!("../../resources/cursor.rgba");
-
 const SUPPORTED_FORMATS: &[Fourcc] = &[
     Fourcc::Abgr2101010,
     Fourcc::Argb2101010,
@@ -82,8 +103,13 @@ const SUPPORTED_FORMATS: &[Fourcc] = &[
     Fourcc::Argb8888,
 ];
 
-pub type GbmDrmCompositor =
-    DrmCompositor<GbmAllocator<DrmDeviceFd>, GbmDevice<DrmDeviceFd>, (), DrmDeviceFd>;
+pub type GbmDrmCompositor = DrmCompositor<
+    GbmAllocator<DrmDeviceFd>,
+    GbmDevice<DrmDeviceFd>,
+    // feedback of the frame, and whether it was rendered while the session is locked
+    (Option<OutputPresentationFeedback>, bool),
+    DrmDeviceFd,
+>;
 
 pub struct UdevData {
     pub session: LibSeatSession,
@@ -91,6 +117,9 @@ pub struct UdevData {
     gpus: GpuManager<GbmGlesBackend<GlowRenderer, DrmDeviceFd>>,
     devices: HashMap<DrmNode, Device>,
     dmabuf_state: Option<(DmabufState, DmabufGlobal)>,
+    output_management_state: OutputManagementState,
+    output_power_management_state: OutputPowerManagementState,
+    gamma_control_manager_state: GammaControlManagerState,
 }
 
 impl DmabufHandler for MagmaState<UdevData> {
@@ -123,6 +152,25 @@ impl Backend for UdevData {
     fn seat_name(&self) -> String {
         self.session.seat()
     }
+
+    fn set_output_power(state: &mut MagmaState<Self>, output: &Output, on: bool) {
+        state.set_output_power(output, on);
+    }
+
+    fn switch_vt(state: &mut MagmaState<Self>, vt: i32) {
+        info!(to = vt, "Trying to switch vt");
+        if let Err(err) = state.backend_data.session.change_vt(vt) {
+            error!(vt, "Error switching vt: {}", err);
+        }
+    }
+
+    fn is_output_powered(&self, output: &Output) -> bool {
+        self.devices
+            .values()
+            .flat_map(|device| device.surfaces.values())
+            .find(|surface| &surface.output == output)
+            .map_or(true, |surface| surface.powered)
+    }
 }
 pub struct Device {
     pub surfaces: HashMap<crtc::Handle, Surface>,
@@ -138,8 +186,25 @@ pub struct Surface {
     _render_node: DrmNode,
     global: GlobalId,
     compositor: GbmDrmCompositor,
+    connector: connector::Info,
     output: Output,
-    pointer_texture: TextureBuffer<GlesTexture>,
+    edid_id: String,
+    cursor_textures: CursorTextures,
+    background: Option<TextureBuffer<GlesTexture>>,
+    // false while turned off (DPMS)
+    powered: bool,
+    // ramps found on connect, restored once no client controls the gamma
+    original_gamma: Option<Vec<u16>>,
+    // ramps set by a wlr-gamma-control client
+    gamma: Option<Vec<u16>>,
+    // the crtc's VRR_ENABLED property, if the monitor supports adaptive sync
+    vrr_property: Option<property::Handle>,
+    vrr: Vrr,
+    vrr_enabled: bool,
+    // whether the window covering the output allows tearing
+    tearing: bool,
+    // whether the last frame put a client buffer on the primary plane
+    direct_scanout: bool,
 }
 
 pub fn init_udev() {
@@ -171,6 +236,15 @@ pub fn init_udev() {
         gpus,
         devices: HashMap::new(),
         dmabuf_state: None,
+        output_management_state: OutputManagementState::new::<MagmaState<UdevData>>(
+            &display_handle,
+        ),
+        output_power_management_state: OutputPowerManagementState::new::<MagmaState<UdevData>>(
+            &display_handle,
+        ),
+        gamma_control_manager_state: GammaControlManagerState::new::<MagmaState<UdevData>>(
+            &display_handle,
+        ),
     };
 
     let mut state = MagmaState::new(event_loop.handle(), event_loop.get_signal(), display, data);
@@ -190,12 +264,7 @@ pub fn init_udev() {
     event_loop
         .handle()
         .insert_source(libinput_backend, move |event, _, calloopdata| {
-            if let Some(vt) = calloopdata.state.process_input_event_udev(event) {
-                info!(to = vt, "Trying to switch vt");
-                if let Err(err) = calloopdata.state.backend_data.session.change_vt(vt) {
-                    error!(vt, "Error switching vt: {}", err);
-                }
-            }
+            calloopdata.state.process_input_event_udev(event);
         })
         .unwrap();
 
@@ -239,6 +308,14 @@ pub fn init_udev() {
                             // has no content and damage tracking may prevent a redraw
                             // otherwise
                             surface.compositor.reset_buffers();
+                            // another session may have changed the gamma ramps meanwhile
+                            if let Some(ramps) =
+                                surface.gamma.as_ref().or(surface.original_gamma.as_ref())
+                            {
+                                if let Err(err) = write_gamma(&backend.drm, crtc, ramps) {
+                                    warn!("Failed to restore gamma ramps: {}", err);
+                                }
+                            }
                             data.state.loop_handle.insert_idle(move |data| {
                                 if let Some(SwapBuffersError::ContextLost(_)) =
                                     data.state.render(node, crtc, None).err()
@@ -316,9 +393,7 @@ pub fn init_udev() {
 
     std::env::set_var("WAYLAND_DISPLAY", &calloopdata.state.socket_name);
 
-    for command in &CONFIG.autostart {
-        process::spawn(command);
-    }
+    calloopdata.state.start_xwayland();
 
     event_loop
         .run(None, &mut calloopdata, move |data| {
@@ -327,16 +402,8 @@ pub fn init_udev() {
                 .all_windows()
                 .for_each(|e| e.refresh());
 
-            let output = data.state.workspaces.current().outputs().next().unwrap();
-            for layer in layer_map_for_output(output).layers() {
-                layer.send_frame(
-                    output,
-                    data.state.start_time.elapsed(),
-                    Some(Duration::ZERO),
-                    |_, _| Some(output.clone()),
-                );
-            }
-
+            data.state.refresh_foreign_toplevels();
+            data.state.send_preferred_scales();
             data.display_handle.flush_clients().unwrap();
             data.state.popup_manager.cleanup();
         })
@@ -371,6 +438,99 @@ pub fn primary_gpu(seat: &str) -> (DrmNode, PathBuf) {
         })
 }
 
+/// Find the connector mode with the given size and the refresh rate (in mHz) closest to `refresh`.
+fn select_drm_mode(
+    connector: &connector::Info,
+    size: Size<i32, Physical>,
+    refresh: i32,
+) -> Option<DrmMode> {
+    connector
+        .modes()
+        .iter()
+        .filter(|mode| {
+            let (x, y) = mode.size();
+            Size::from((x as i32, y as i32)) == size
+        })
+        // and then select the closest refresh rate (e.g. to match 59.98 as 60)
+        .min_by_key(|mode| {
+            let refresh_rate = WlMode::from(**mode).refresh;
+            (refresh - refresh_rate).abs()
+        })
+        .copied()
+}
+
+/// Find the property of a drm object by name, with its current value.
+fn find_property(
+    drm: &DrmDevice,
+    handle: impl ResourceHandle,
+    name: &str,
+) -> Option<(property::Handle, property::RawValue)> {
+    let props = drm.get_properties(handle).ok()?;
+    let (ids, values) = props.as_props_and_values();
+    ids.iter().zip(values).find_map(|(id, value)| {
+        let info = drm.get_property(*id).ok()?;
+        (info.name().to_str().ok()? == name).then_some((*id, *value))
+    })
+}
+
+/// Read the serial number of the monitor from the connector's EDID blob.
+fn edid_serial(drm: &DrmDevice, connector: connector::Handle) -> Option<String> {
+    let (_, blob) = find_property(drm, connector, "EDID")?;
+    let edid = drm.get_property_blob(blob).ok()?;
+
+    // prefer the serial number string from the display descriptors
+    edid.get(54..126)?
+        .chunks_exact(18)
+        .find(|descriptor| descriptor[..3] == [0, 0, 0] && descriptor[3] == 0xff)
+        .map(|descriptor| String::from_utf8_lossy(&descriptor[5..]).trim().to_string())
+        .or_else(|| {
+            let serial = u32::from_le_bytes(edid.get(12..16)?.try_into().ok()?);
+            (serial != 0).then(|| serial.to_string())
+        })
+}
+
+/// Resolve the mode of a configured output, falling back to the configured modeline
+/// if the connector does not advertise the requested mode.
+fn configured_drm_mode(
+    connector: &connector::Info,
+    output_config: &OutputConfig,
+) -> Result<DrmMode, String> {
+    let size = output_config.mode_size();
+    let refresh = output_config.mode_refresh();
+    if let Some(mode) = select_drm_mode(connector, size, refresh as i32) {
+        return Ok(mode);
+    }
+    match output_config.modeline() {
+        Some(modeline) if modeline.eq_ignore_ascii_case("cvt") => {
+            info!(
+                "Generating CVT mode {}x{}@{}",
+                size.w,
+                size.h,
+                refresh / 1000
+            );
+            u32::try_from(size.w)
+                .ok()
+                .zip(u32::try_from(size.h).ok())
+                .and_then(|(w, h)| modeline::cvt(w, h, refresh as f64 / 1000.0))
+                .ok_or_else(|| {
+                    format!(
+                        "no CVT mode can be generated for {}x{}@{}",
+                        size.w,
+                        size.h,
+                        refresh / 1000
+                    )
+                })
+        }
+        Some(modeline) => modeline::parse_modeline(modeline),
+        None => Err(format!(
+            "no mode matching {}x{}@{} is advertised",
+            size.w,
+            size.h,
+            refresh / 1000
+        )),
+    }
+}
+
 // Udev
 impl MagmaState<UdevData> {
     pub fn on_udev_event(&mut self, event: UdevEvent, display: &mut DisplayHandle) {
@@ -462,15 +622,47 @@ impl MagmaState<UdevData> {
                 .as_mut()
                 .remove_node(&device.render_node);
 
-            for surface in device.surfaces.values() {
-                self.dh
-                    .disable_global::<MagmaState<UdevData>>(surface.global.clone());
-                for workspace in self.workspaces.iter() {
-                    workspace.remove_output(&surface.output)
-                }
+            let surfaces: Vec<Surface> = device.surfaces.drain().map(|(_, s)| s).collect();
+            for surface in surfaces {
+                self.on_output_disconnected(surface);
             }
         }
     }
+
+    fn on_output_disconnected(&mut self, surface: Surface) {
+        let output = surface.output;
+        info!("Output disconnected, name: {}", output.name());
+
+        self.dh
+            .disable_global::<MagmaState<UdevData>>(surface.global.clone());
+        // give clients some time to release the output before destroying the global
+        let global = surface.global;
+        self.loop_handle
+            .insert_source(
+                Timer::from_duration(Duration::from_secs(10)),
+                move |_, _, data| {
+                    data.display_handle
+                        .remove_global::<MagmaState<UdevData>>(global.clone());
+                    TimeoutAction::Drop
+                },
+            )
+            .expect("failed to schedule global removal");
+
+        for layer in layer_map_for_output(&output).layers() {
+            layer.layer_surface().send_close();
+        }
+        self.backend_data
+            .output_management_state
+            .remove_head(&output);
+        self.backend_data
+            .output_power_management_state
+            .remove_output(&output);
+        self.backend_data
+            .gamma_control_manager_state
+            .remove_output(&output);
+        self.workspaces.disconnect_output(&output, surface.edid_id);
+        self.set_input_focus_auto();
+    }
 }
 
 // Drm
@@ -479,13 +671,41 @@ impl MagmaState<UdevData> {
         &mut self,
         node: DrmNode,
         event: drm::DrmEvent,
-        _meta: &mut Option<drm::DrmEventMetadata>,
+        meta: &mut Option<drm::DrmEventMetadata>,
     ) {
         match event {
             drm::DrmEvent::VBlank(crtc) => {
                 let device = self.backend_data.devices.get_mut(&node).unwrap();
                 let surface = device.surfaces.get_mut(&crtc).unwrap();
-                surface.compositor.frame_submitted().ok();
+                // without a hardware timestamp, the event arriving is as close as it gets
+                let (time, flags) = match meta.as_ref().map(|meta| &meta.time) {
+                    Some(DrmEventTime::Monotonic(time)) => (
+                        (*time).into(),
+                        wp_presentation_feedback::Kind::Vsync
+                            | wp_presentation_feedback::Kind::HwClock
+                            | wp_presentation_feedback::Kind::HwCompletion,
+                    ),
+                    _ => (self.clock.now(), wp_presentation_feedback::Kind::Vsync),
+                };
+                let sequence = meta.as_ref().map_or(0, |meta| meta.sequence as u64);
+                match surface.compositor.frame_submitted() {
+                    Ok(Some((feedback, locked))) => {
+                        if let Some(feedback) = feedback {
+                            feedback.presented(
+                                time,
+                                refresh_interval(&surface.output),
+                                sequence,
+                                flags,
+                            );
+                        }
+                        if let Some(lock) = self.session_lock.as_mut().filter(|_| locked) {
+                            lock.frame_presented(&surface.output);
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(err) => warn!("Failed to submit frame: {}", err),
+                }
+                self.confirm_session_lock();
                 #[cfg(feature = "debug")]
                 self.debug.fps.displayed();
                 debug!("VBlank event on {:?}", crtc);
@@ -525,37 +745,62 @@ impl MagmaState<UdevData> {
                     connector.interface_id()
                 );
                 info!("New output connected, name: {}", name);
-                let drm_mode = if CONFIG.outputs.contains_key(&name) {
-                    let output_config = &CONFIG.outputs[&name];
-                    *connector
-                        .modes()
-                        .iter()
-                        .filter(|mode| {
-                            let (x, y) = mode.size();
-                            Size::from((x as i32, y as i32)) == output_config.mode_size()
-                        })
-                        // and then select the closest refresh rate (e.g. to match 59.98 as 60)
-                        .min_by_key(|mode| {
-                            let refresh_rate = WlMode::from(**mode).refresh;
-                            (output_config.mode_refresh() as i32 - refresh_rate).abs()
-                        })
-                        .expect("No matching mode found for output config")
-                } else {
-                    *connector
-                        .modes()
-                        .iter()
-                        .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
-                        .unwrap_or(&connector.modes()[0])
+                let preferred_mode = match connector
+                    .modes()
+                    .iter()
+                    .find(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+                    .or_else(|| connector.modes().first())
+                {
+                    Some(mode) => *mode,
+                    None => {
+                        error!("Output {} does not advertise any modes", name);
+                        return;
+                    }
+                };
+                let mut drm_mode = match CONFIG.outputs.get(&name) {
+                    Some(output_config) => configured_drm_mode(&connector, output_config)
+                        .unwrap_or_else(|err| {
+                            error!(
+                                "Invalid mode for output {}: {}, using preferred mode",
+                                name, err
+                            );
+                            preferred_mode
+                        }),
+                    None => preferred_mode,
                 };
 
-                let drm_surface = device
+                let drm_surface = match device
                     .drm
                     .create_surface(crtc, drm_mode, &[connector.handle()])
-                    .unwrap();
+                    .or_else(|err| {
+                        if drm_mode == preferred_mode {
+                            return Err(err);
+                        }
+                        warn!(
+                            "Failed to set mode for output {}: {}, using preferred mode",
+                            name, err
+                        );
+                        drm_mode = preferred_mode;
+                        device
+                            .drm
+                            .create_surface(crtc, drm_mode, &[connector.handle()])
+                    }) {
+                    Ok(drm_surface) => drm_surface,
+                    Err(err) => {
+                        error!("Failed to create surface for output {}: {}", name, err);
+                        return;
+                    }
+                };
 
                 let (make, model) = EdidInfo::for_connector(&device.drm, connector.handle())
                     .map(|info| (info.manufacturer, info.model))
                     .unwrap_or_else(|| ("Unknown".into(), "Unknown".into()));
+                // identifies the monitor across reconnects, together with the connector for
+                // monitors without (or with the same) serial number
+                let edid_id = match edid_serial(&device.drm, connector.handle()) {
+                    Some(serial) => format!("{} {} {}", make, model, serial),
+                    None => format!("{} {}", make, model),
+                };
 
                 let (w, h) = connector.size().unwrap_or((0, 0));
                 let output = Output::new(
@@ -568,14 +813,14 @@ impl MagmaState<UdevData> {
                     },
                 );
                 let global = output.create_global::<MagmaState<UdevData>>(display);
+                for mode in connector.modes() {
+                    output.add_mode(WlMode::from(*mode));
+                }
                 let output_mode = WlMode::from(drm_mode);
                 output.set_preferred(output_mode);
-                output.change_current_state(
-                    Some(output_mode),
-                    Some(Transform::Normal),
-                    Some(smithay::output::Scale::Integer(1)),
-                    None,
-                );
+                // the compositor is created for the current mode, the rest of the state is
+                // applied once the output is set up
+                output.change_current_state(Some(output_mode), None, None, None);
                 let render_formats = renderer
                     .as_mut()
                     .egl_context()
@@ -611,7 +856,7 @@ impl MagmaState<UdevData> {
                     planes.overlay = vec![];
                 }
 
-                let compositor = GbmDrmCompositor::new(
+                let compositor = match GbmDrmCompositor::new(
                     &output,
                     drm_surface,
                     Some(planes),
@@ -621,42 +866,65 @@ impl MagmaState<UdevData> {
                     render_formats,
                     device.drm.cursor_size(),
                     None,
-                )
-                .unwrap();
+                ) {
+                    Ok(compositor) => compositor,
+                    Err(err) => {
+                        error!("Failed to initialize output {}: {:?}", output.name(), err);
+                        return;
+                    }
+                };
 
-                let pointer_texture = TextureBuffer::from_memory(
-                    renderer.as_mut(),
-                    CURSOR_DATA,
-                    Fourcc::Abgr8888,
-                    (64, 64),
-                    false,
-                    2,
-                    Transform::Normal,
-                    None,
-                )
-                .unwrap();
                 init_shaders(renderer.as_mut());
+                let vrr_property = find_property(&device.drm, connector.handle(), "vrr_capable")
+                    .filter(|(_, capable)| *capable != 0)
+                    .and_then(|_| find_property(&device.drm, crtc, "VRR_ENABLED"));
+                let vrr_enabled = vrr_property.is_some_and(|(_, enabled)| enabled != 0);
+                let vrr_property = vrr_property.map(|(property, _)| property);
                 let surface = Surface {
                     _device_id: node,
                     _render_node: device.render_node,
                     global,
                     compositor,
+                    connector,
                     output: output.clone(),
-                    pointer_texture,
+                    edid_id: edid_id.clone(),
+                    cursor_textures: CursorTextures::default(),
+                    background: None,
+                    powered: true,
+                    original_gamma: read_gamma(&device.drm, crtc),
+                    gamma: None,
+                    vrr_property,
+                    vrr: CONFIG.outputs.get(&name).map_or(Vrr::Off, |c| c.vrr()),
+                    vrr_enabled,
+                    tearing: false,
+                    direct_scanout: false,
                 };
+                let adaptive_sync = surface.vrr_property.is_some() && surface.vrr != Vrr::Off;
 
-                for workspace in self.workspaces.iter() {
-                    workspace.add_output(output.clone())
-                }
+                self.workspaces.connect_output(&output, &edid_id);
 
                 device.surfaces.insert(crtc, surface);
-
-                self.render(node, crtc, None).ok();
+                self.backend_data
+                    .output_management_state
+                    .add_head::<MagmaState<UdevData>>(&output, adaptive_sync);
+
+                if let Err(err) = self.apply_output_state(
+                    node,
+                    crtc,
+                    Some(drm_mode),
+                    Some(Transform::Normal),
+                    Some(1.0),
+                    None,
+                ) {
+                    warn!("Failed to set up output {}: {:?}", output.name(), err);
+                }
             }
             DrmScanEvent::Disconnected {
                 crtc: Some(crtc), ..
             } => {
-                device.surfaces.remove(&crtc);
+                if let Some(surface) = device.surfaces.remove(&crtc) {
+                    self.on_output_disconnected(surface);
+                }
             }
             _ => {}
         }
@@ -670,6 +938,16 @@ impl MagmaState<UdevData> {
         crtc: crtc::Handle,
         screencopy: Option<Screencopy>,
     ) -> Result<bool, SwapBuffersError> {
+        // outputs turned off are not rendered until they are turned on again
+        if self
+            .backend_data
+            .devices
+            .get(&node)
+            .and_then(|device| device.surfaces.get(&crtc))
+            .is_some_and(|surface| !surface.powered)
+        {
+            return Ok(true);
+        }
         #[cfg(feature = "debug")]
         self.debug.fps.start();
         let device = self.backend_data.devices.get_mut(&node).unwrap();
@@ -679,7 +957,8 @@ impl MagmaState<UdevData> {
             .gpus
             .single_renderer(&device.render_node)
             .unwrap();
-        let output = self.workspaces.current().outputs().next().unwrap();
+        let output = &surface.output.clone();
+        let scale = Scale::from(output.current_scale().fractional_scale());
 
         let mut renderelements: Vec<CustomRenderElements<MultiRenderer<_, _>>> = vec![];
         let render_cursor = if let Some(screencopy) = &screencopy {
@@ -688,18 +967,15 @@ impl MagmaState<UdevData> {
             true
         };
         if render_cursor {
-            renderelements.append(&mut vec![
-                CustomRenderElements::<MultiRenderer<_, _>>::from(
-                    TextureRenderElement::from_texture_buffer(
-                        self.pointer_location.to_physical(Scale::from(1.0)),
-                        &surface.pointer_texture,
-                        None,
-                        None,
-                        None,
-                        Kind::Cursor,
-                    ),
-                ),
-            ]);
+            renderelements.extend(cursor_elements(
+                &mut renderer,
+                &mut self.cursor_theme,
+                &mut surface.cursor_textures,
+                &self.cursor_status,
+                self.pointer_location,
+                self.start_time.elapsed(),
+                scale,
+            ));
         }
         #[cfg(feature = "debug")]
         if self.debug.active {
@@ -708,78 +984,111 @@ impl MagmaState<UdevData> {
                     .global_ui(
                         Some(&node),
                         output,
+                        Some(surface.direct_scanout),
                         &self.seat,
                         renderer.as_mut(),
-                        Rectangle::from_loc_and_size(
-                            (0, 0),
-                            output.current_mode().unwrap().size.to_logical(1),
-                        ),
-                        1.0,
+                        Rectangle::from_loc_and_size((0, 0), output_size(output)),
+                        scale.x,
                         0.8,
                     )
                     .unwrap()
                     .into(),
             );
         }
-        let layer_map = layer_map_for_output(output);
-        let (lower, upper): (Vec<&LayerSurface>, Vec<&LayerSurface>) = layer_map
-            .layers()
-            .rev()
-            .partition(|s| matches!(s.layer(), Layer::Background | Layer::Bottom));
-
-        renderelements.extend(
-            upper
-                .into_iter()
-                .filter_map(|surface| {
-                    layer_map
-                        .layer_geometry(surface)
-                        .map(|geo| (geo.loc, surface))
-                })
-                .flat_map(|(loc, surface)| {
-                    AsRenderElements::<MultiRenderer<_, _>>::render_elements::<
-                        WaylandSurfaceRenderElement<MultiRenderer<_, _>>,
-                    >(
-                        surface,
-                        &mut renderer,
-                        loc.to_physical_precise_round(1),
-                        Scale::from(1.0),
-                        1.0,
-                    )
+        let clear = if let Some(lock) = &self.session_lock {
+            renderelements.extend(lock_elements(lock, output, &mut renderer));
+            LOCKED_COLOR
+        } else {
+            renderelements.extend(dnd_icon_elements(
+                self.dnd_icon.as_ref(),
+                self.pointer_location,
+                &mut renderer,
+                scale,
+            ));
+            renderelements.extend(override_redirect_elements(
+                &self.override_redirect,
+                &mut renderer,
+                scale,
+            ));
+            let layer_map = layer_map_for_output(output);
+            let (lower, upper): (Vec<&LayerSurface>, Vec<&LayerSurface>) = layer_map
+                .layers()
+                .rev()
+                .partition(|s| matches!(s.layer(), Layer::Background | Layer::Bottom));
+
+            renderelements.extend(
+                upper
                     .into_iter()
-                    .map(CustomRenderElements::Surface)
-                }),
-        );
+                    .filter_map(|surface| {
+                        layer_map
+                            .layer_geometry(surface)
+                            .map(|geo| (geo.loc, surface))
+                    })
+                    .flat_map(|(loc, surface)| {
+                        AsRenderElements::<MultiRenderer<_, _>>::render_elements::<
+                            WaylandSurfaceRenderElement<MultiRenderer<_, _>>,
+                        >(
+                            surface,
+                            &mut renderer,
+                            loc.to_physical_precise_round(scale),
+                            scale,
+                            1.0,
+                        )
+                        .into_iter()
+                        .map(CustomRenderElements::Surface)
+                    }),
+            );
 
-        renderelements.extend(self.workspaces.current().render_elements(&mut renderer));
+            renderelements.extend(self.workspaces.current().render_elements(
+                &mut renderer,
+                output,
+                scale,
+            ));
 
-        renderelements.extend(
-            lower
-                .into_iter()
-                .filter_map(|surface| {
-                    layer_map
-                        .layer_geometry(surface)
-                        .map(|geo| (geo.loc, surface))
-                })
-                .flat_map(|(loc, surface)| {
-                    AsRenderElements::<MultiRenderer<_, _>>::render_elements::<
-                        WaylandSurfaceRenderElement<MultiRenderer<_, _>>,
-                    >(
-                        surface,
-                        &mut renderer,
-                        loc.to_physical_precise_round(1),
-                        Scale::from(1.0),
-                        1.0,
-                    )
+            renderelements.extend(
+                lower
                     .into_iter()
-                    .map(CustomRenderElements::Surface)
-                }),
-        );
+                    .filter_map(|surface| {
+                        layer_map
+                            .layer_geometry(surface)
+                            .map(|geo| (geo.loc, surface))
+                    })
+                    .flat_map(|(loc, surface)| {
+                        AsRenderElements::<MultiRenderer<_, _>>::render_elements::<
+                            WaylandSurfaceRenderElement<MultiRenderer<_, _>>,
+                        >(
+                            surface,
+                            &mut renderer,
+                            loc.to_physical_precise_round(scale),
+                            scale,
+                            1.0,
+                        )
+                        .into_iter()
+                        .map(CustomRenderElements::Surface)
+                    }),
+            );
+
+            if let Some(background) = &surface.background {
+                renderelements.push(CustomRenderElements::from(
+                    TextureRenderElement::from_texture_buffer(
+                        (0.0, 0.0),
+                        background,
+                        None,
+                        None,
+                        // the texture has the transformed size of the mode
+                        Some(output_size(output)),
+                        Kind::Unspecified,
+                    ),
+                ));
+            }
+            clear_color(output)
+        };
         #[cfg(feature = "debug")]
         self.debug.fps.elements();
 
         let frame_result: Result<RenderFrameResult<_, _, _>, SwapBuffersError> = surface
             .compositor
-            .render_frame::<_, _>(&mut renderer, &renderelements, [0.1, 0.1, 0.1, 1.0])
+            .render_frame::<_, _>(&mut renderer, &renderelements, clear)
             .map_err(|err| match err {
                 smithay::backend::drm::compositor::RenderFrameError::PrepareFrame(err) => {
                     err.into()
@@ -872,23 +1181,47 @@ impl MagmaState<UdevData> {
             self.debug.fps.screencopy();
         }
 
-        let mut result = match frame_result {
-            Ok(frame_result) => Ok(!frame_result.is_empty),
-            Err(frame_result) => Err(frame_result),
+        BorderShader::cleanup(renderer.as_mut());
+        let (mut result, states) = match frame_result {
+            Ok(frame_result) => {
+                let direct_scanout = matches!(
+                    frame_result.primary_element,
+                    smithay::backend::drm::compositor::PrimaryPlaneElement::Element(_)
+                );
+                if direct_scanout != surface.direct_scanout {
+                    surface.direct_scanout = direct_scanout;
+                    debug!(
+                        direct_scanout,
+                        "Direct scanout changed on {}",
+                        output.name()
+                    );
+                }
+                (Ok(!frame_result.is_empty), Some(frame_result.states))
+            }
+            Err(err) => (Err(err), None),
         };
+        drop(renderelements);
+        drop(renderer);
 
-        if let Ok(rendered) = result {
-            if rendered {
-                let queueresult = surface
-                    .compositor
-                    .queue_frame(())
-                    .map_err(Into::<SwapBuffersError>::into);
-                if let Err(queueresult) = queueresult {
-                    result = Err(queueresult);
-                }
+        if let Some(states) = &states {
+            self.update_primary_scanout_output(output, states);
+        }
+        if let (Ok(true), Some(states)) = (&result, &states) {
+            let feedback = self.take_presentation_feedback(output, states);
+            let surface = self
+                .backend_data
+                .devices
+                .get_mut(&node)
+                .and_then(|device| device.surfaces.get_mut(&crtc))
+                .unwrap();
+            let locked = self.session_lock.is_some();
+            if let Err(err) = surface.compositor.queue_frame((Some(feedback), locked)) {
+                result = Err(err.into());
             }
         }
 
+        self.update_sync_mode(node, crtc, output);
+
         let reschedule = match &result {
             Ok(has_rendered) => !has_rendered,
             Err(err) => {
@@ -939,19 +1272,383 @@ impl MagmaState<UdevData> {
                 .expect("failed to schedule frame timer");
         }
 
-        self.workspaces.current().windows().for_each(|window| {
-            window.send_frame(
-                output,
-                self.start_time.elapsed(),
-                Some(Duration::ZERO),
-                |_, _| Some(output.clone()),
-            );
-        });
-        BorderShader::cleanup(renderer.as_mut());
+        self.send_frames(output);
         result
     }
 }
 
+impl MagmaState<UdevData> {
+    // follow the vrr mode of the output and the presentation hint of the window covering it
+    fn update_sync_mode(&mut self, node: DrmNode, crtc: crtc::Handle, output: &Output) {
+        let fullscreen = self
+            .workspaces
+            .current()
+            .fullscreen_window(output)
+            .is_some();
+        let tearing = self.allows_tearing(output);
+        let Some(device) = self.backend_data.devices.get_mut(&node) else {
+            return;
+        };
+        let Some(surface) = device.surfaces.get_mut(&crtc) else {
+            return;
+        };
+
+        // the DrmCompositor of the smithay revision in use can't queue async page flips, so
+        // frames of windows allowing tearing still wait for the vblank
+        if tearing != surface.tearing {
+            surface.tearing = tearing;
+            info!(tearing, "Tearing hint of {} changed", output.name());
+        }
+
+        let Some(property) = surface.vrr_property else {
+            return;
+        };
+        let vrr = match surface.vrr {
+            Vrr::Off => false,
+            Vrr::On => true,
+            Vrr::OnlyFullscreen => fullscreen,
+        };
+        if vrr == surface.vrr_enabled {
+            return;
+        }
+        // the property can be busy while a commit is pending, so it is tried again after the
+        // next frame until it sticks
+        match device.drm.set_property(crtc, property, vrr as u64) {
+            Ok(()) => {
+                surface.vrr_enabled = vrr;
+                info!(vrr, "Adaptive sync changed on {}", output.name());
+            }
+            Err(err) => debug!("Failed to set adaptive sync on {}: {}", output.name(), err),
+        }
+    }
+
+    fn surface_for_output(&self, output: &Output) -> Option<(DrmNode, crtc::Handle)> {
+        self.backend_data.devices.iter().find_map(|(node, device)| {
+            device
+                .surfaces
+                .iter()
+                .find(|(_, surface)| &surface.output == output)
+                .map(|(crtc, _)| (*node, *crtc))
+        })
+    }
+
+    /// Turn an output off by disabling its crtc, or back on with a full redraw.
+    pub fn set_output_power(&mut self, output: &Output, on: bool) {
+        let Some((node, crtc)) = self.surface_for_output(output) else {
+            return;
+        };
+        let device = self.backend_data.devices.get_mut(&node).unwrap();
+        let surface = device.surfaces.get_mut(&crtc).unwrap();
+        if surface.powered == on {
+            return;
+        }
+        surface.powered = on;
+        if on {
+            surface.compositor.reset_buffers();
+            self.render(node, crtc, None).ok();
+        } else if let Err(err) = surface.compositor.clear() {
+            warn!("Failed to turn off output {}: {}", output.name(), err);
+        }
+        self.backend_data
+            .output_power_management_state
+            .mode_changed(output, on);
+        self.backend_data
+            .output_management_state
+            .set_head_enabled(output, on);
+        // a locker waiting for this output doesn't need to anymore
+        self.confirm_session_lock();
+    }
+
+    /// Apply gamma ramps to the crtc of an output, or restore the original ones with `None`.
+    pub fn set_gamma(&mut self, output: &Output, ramps: Option<Vec<u16>>) -> bool {
+        let Some((node, crtc)) = self.surface_for_output(output) else {
+            return false;
+        };
+        let session_active = self.backend_data.session.is_active();
+        let device = self.backend_data.devices.get_mut(&node).unwrap();
+        let surface = device.surfaces.get_mut(&crtc).unwrap();
+        // applied once the session is active again
+        if session_active {
+            if let Some(applied) = ramps.as_ref().or(surface.original_gamma.as_ref()) {
+                if let Err(err) = write_gamma(&device.drm, crtc, applied) {
+                    warn!("Failed to set gamma of output {}: {}", output.name(), err);
+                    return false;
+                }
+            }
+        }
+        surface.gamma = ramps;
+        true
+    }
+
+    /// Check whether the crtc of an output can drive a mode with a test-only commit, without
+    /// touching the state of its surface.
+    fn test_drm_mode(&self, node: DrmNode, crtc: crtc::Handle, drm_mode: Option<DrmMode>) -> bool {
+        let device = &self.backend_data.devices[&node];
+        let surface = &device.surfaces[&crtc];
+        let Some(drm_mode) =
+            drm_mode.filter(|mode| *mode != surface.compositor.surface().pending_mode())
+        else {
+            return true;
+        };
+        // legacy devices can't test a modeset, so only advertised modes are accepted
+        if !device.drm.is_atomic() {
+            return surface.connector.modes().contains(&drm_mode);
+        }
+
+        let connector = surface.connector.handle();
+        let (Some((crtc_id, _)), Some((mode_id, _)), Some((active, _))) = (
+            find_property(&device.drm, connector, "CRTC_ID"),
+            find_property(&device.drm, crtc, "MODE_ID"),
+            find_property(&device.drm, crtc, "ACTIVE"),
+        ) else {
+            return false;
+        };
+        let blob = match device.drm.create_property_blob(&drm_mode) {
+            Ok(blob) => blob,
+            Err(err) => {
+                warn!("Failed to create mode blob: {}", err);
+                return false;
+            }
+        };
+        let mut request = AtomicModeReq::new();
+        request.add_property(connector, crtc_id, property::Value::CRTC(Some(crtc)));
+        request.add_property(crtc, mode_id, blob);
+        request.add_property(crtc, active, property::Value::Boolean(true));
+        let result = device.drm.atomic_commit(
+            AtomicCommitFlags::ALLOW_MODESET | AtomicCommitFlags::TEST_ONLY,
+            request,
+        );
+        if let property::Value::Blob(blob) = blob {
+            if let Err(err) = device.drm.destroy_property_blob(blob) {
+                warn!("Failed to destroy mode blob: {}", err);
+            }
+        }
+        result.is_ok()
+    }
+
+    /// Change the mode and output state of an output and re-layout its contents, both when it
+    /// is connected and when it is reconfigured.
+    pub fn apply_output_state(
+        &mut self,
+        node: DrmNode,
+        crtc: crtc::Handle,
+        drm_mode: Option<DrmMode>,
+        transform: Option<Transform>,
+        scale: Option<f64>,
+        location: Option<Point<i32, Logical>>,
+    ) -> Result<(), SwapBuffersError> {
+        let device = self.backend_data.devices.get_mut(&node).unwrap();
+        let surface = device.surfaces.get_mut(&crtc).unwrap();
+
+        if let Some(drm_mode) = drm_mode {
+            if surface.compositor.surface().pending_mode() != drm_mode {
+                surface
+                    .compositor
+                    .use_mode(drm_mode)
+                    .map_err(Into::<SwapBuffersError>::into)?;
+            }
+        }
+        let output = surface.output.clone();
+        output.change_current_state(
+            drm_mode.map(WlMode::from),
+            transform,
+            scale.map(smithay::output::Scale::Fractional),
+            location,
+        );
+        if drm_mode.is_some() || transform.is_some() {
+            // the background is rendered at the transformed size of the mode
+            if let Ok(mut renderer) = self.backend_data.gpus.single_renderer(&device.render_node) {
+                surface.background =
+                    background_texture(renderer.as_mut(), &output, &mut self.background_images);
+            }
+        }
+
+        layer_map_for_output(&output).arrange();
+        for workspace in self.workspaces.iter() {
+            bsp_update_layout(workspace);
+        }
+        self.render(node, crtc, None).ok();
+        Ok(())
+    }
+}
+
+impl OutputManagementHandler for MagmaState<UdevData> {
+    fn output_management_state(&mut self) -> &mut OutputManagementState {
+        &mut self.backend_data.output_management_state
+    }
+
+    fn apply_output_configuration(
+        &mut self,
+        heads: Vec<HeadConfiguration>,
+        disabled: Vec<Output>,
+        test_only: bool,
+    ) -> bool {
+        if disabled
+            .iter()
+            .any(|output| self.surface_for_output(output).is_none())
+        {
+            return false;
+        }
+        let mut pending = Vec::with_capacity(heads.len());
+        for head in heads {
+            let (node, crtc) = match self.surface_for_output(&head.output) {
+                Some(surface) => surface,
+                None => return false,
+            };
+            let surface = &self.backend_data.devices[&node].surfaces[&crtc];
+            // adaptive sync can only be enabled if the monitor supports it
+            if head.adaptive_sync == Some(true) && surface.vrr_property.is_none() {
+                return false;
+            }
+            let drm_mode = match head.mode {
+                Some(ModeConfiguration::Mode(mode)) => {
+                    let current_mode = surface.compositor.surface().pending_mode();
+                    match select_drm_mode(&surface.connector, mode.size, mode.refresh) {
+                        Some(drm_mode) => Some(drm_mode),
+                        // custom modes are not part of the connector's mode list
+                        None if WlMode::from(current_mode) == mode => Some(current_mode),
+                        None => return false,
+                    }
+                }
+                Some(ModeConfiguration::Custom { size, refresh }) => {
+                    let refresh = refresh.unwrap_or(60_000);
+                    // DRM modes can't be larger
+                    let (Ok(w), Ok(h)) = (u16::try_from(size.w), u16::try_from(size.h)) else {
+                        return false;
+                    };
+                    match select_drm_mode(&surface.connector, size, refresh)
+                        .or_else(|| modeline::cvt(w.into(), h.into(), refresh as f64 / 1000.0))
+                    {
+                        Some(drm_mode) => Some(drm_mode),
+                        None => return false,
+                    }
+                }
+                None => None,
+            };
+            pending.push((node, crtc, drm_mode, head));
+        }
+
+        if test_only {
+            return pending
+                .iter()
+                .all(|(node, crtc, drm_mode, _)| self.test_drm_mode(*node, *crtc, *drm_mode));
+        }
+
+        // state of the heads applied so far, restored if a later one fails
+        let mut applied = Vec::with_capacity(pending.len());
+        for (node, crtc, drm_mode, head) in pending {
+            let previous = (
+                node,
+                crtc,
+                self.backend_data.devices[&node].surfaces[&crtc]
+                    .compositor
+                    .surface()
+                    .pending_mode(),
+                head.output.current_transform(),
+                head.output.current_scale().fractional_scale(),
+                head.output.current_location(),
+            );
+            if let Err(err) = self.apply_output_state(
+                node,
+                crtc,
+                drm_mode,
+                head.transform,
+                head.scale,
+                head.position,
+            ) {
+                warn!(
+                    "Failed to apply configuration to output {}: {:?}",
+                    head.output.name(),
+                    err
+                );
+                for (node, crtc, drm_mode, transform, scale, location) in applied.into_iter().rev()
+                {
+                    if let Err(err) = self.apply_output_state(
+                        node,
+                        crtc,
+                        Some(drm_mode),
+                        Some(transform),
+                        Some(scale),
+                        Some(location),
+                    ) {
+                        warn!("Failed to restore output configuration: {:?}", err);
+                    }
+                }
+                return false;
+            }
+            applied.push(previous);
+
+            if let Some(adaptive_sync) = head.adaptive_sync {
+                let surface = self
+                    .backend_data
+                    .devices
+                    .get_mut(&node)
+                    .unwrap()
+                    .surfaces
+                    .get_mut(&crtc)
+                    .unwrap();
+                surface.vrr = match (adaptive_sync, surface.vrr) {
+                    (false, _) => Vrr::Off,
+                    // keep following fullscreen windows if configured to
+                    (true, Vrr::Off) => Vrr::On,
+                    (true, vrr) => vrr,
+                };
+                self.update_sync_mode(node, crtc, &head.output);
+                self.backend_data
+                    .output_management_state
+                    .set_head_adaptive_sync(&head.output, adaptive_sync);
+            }
+            // enabling a head turns it back on
+            self.set_output_power(&head.output, true);
+        }
+        // disabled heads are turned off like with wlr-output-power-management
+        for output in &disabled {
+            self.set_output_power(output, false);
+        }
+        true
+    }
+}
+
+delegate_output_management!(MagmaState<UdevData>);
+
+impl OutputPowerManagementHandler for MagmaState<UdevData> {
+    fn output_power_management_state(&mut self) -> &mut OutputPowerManagementState {
+        &mut self.backend_data.output_power_management_state
+    }
+
+    fn output_powered(&self, output: &Output) -> bool {
+        self.backend_data.is_output_powered(output)
+    }
+
+    fn set_output_powered(&mut self, output: &Output, on: bool) {
+        self.set_output_power(output, on);
+    }
+}
+
+delegate_output_power_management!(MagmaState<UdevData>);
+
+impl GammaControlHandler for MagmaState<UdevData> {
+    fn gamma_control_manager_state(&mut self) -> &mut GammaControlManagerState {
+        &mut self.backend_data.gamma_control_manager_state
+    }
+
+    fn gamma_size(&self, output: &Output) -> Option<u32> {
+        let (node, crtc) = self.surface_for_output(output)?;
+        let surface = &self.backend_data.devices[&node].surfaces[&crtc];
+        // without the original ramps they could not be restored
+        surface
+            .original_gamma
+            .as_ref()
+            .map(|ramps| (ramps.len() / 3) as u32)
+    }
+
+    fn set_gamma(&mut self, output: &Output, ramps: Option<Vec<u16>>) -> bool {
+        MagmaState::set_gamma(self, output, ramps)
+    }
+}
+
+delegate_gamma_control!(MagmaState<UdevData>);
+
 impl ScreencopyHandler for MagmaState<UdevData> {
     fn output(&mut self, output: &WlOutput) -> &Output {
         self.workspaces.outputs().find(|o| o.owns(output)).unwrap()
@@ -970,3 +1667,29 @@ impl ScreencopyHandler for MagmaState<UdevData> {
 }
 
 delegate_screencopy_manager!(MagmaState<UdevData>);
+
+// the gamma ramps of a crtc, red, green and blue one after another
+fn read_gamma(drm: &DrmDevice, crtc: crtc::Handle) -> Option<Vec<u16>> {
+    let size = drm.get_crtc(crtc).ok()?.gamma_length() as usize;
+    if size == 0 {
+        return None;
+    }
+    let mut ramps = vec![0; size * 3];
+    let (red, rest) = ramps.split_at_mut(size);
+    let (green, blue) = rest.split_at_mut(size);
+    if let Err(err) = drm.get_gamma(crtc, red, green, blue) {
+        warn!("Failed to read gamma ramps: {}", err);
+        return None;
+    }
+    Some(ramps)
+}
+
+fn write_gamma(drm: &DrmDevice, crtc: crtc::Handle, ramps: &[u16]) -> io::Result<()> {
+    let size = ramps.len() / 3;
+    drm.set_gamma(
+        crtc,
+        &ramps[..size],
+        &ramps[size..size * 2],
+        &ramps[size * 2..],
+    )
+}
@@ -5,8 +5,14 @@ use smithay::{
         allocator::dmabuf::Dmabuf,
         egl::EGLDevice,
         renderer::{
-            damage::OutputDamageTracker, element::AsRenderElements, glow::GlowRenderer, ImportDma,
-            ImportEgl,
+            damage::OutputDamageTracker,
+            element::{
+                texture::{TextureBuffer, TextureRenderElement},
+                AsRenderElements, Kind,
+            },
+            gles::GlesTexture,
+            glow::GlowRenderer,
+            ImportDma, ImportEgl,
         },
         winit::{self, WinitEvent, WinitEventLoop, WinitGraphicsBackend},
     },
@@ -18,6 +24,7 @@ use smithay::{
             timer::{TimeoutAction, Timer},
             EventLoop,
         },
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
         wayland_server::{Display, DisplayHandle},
         winit::platform::pump_events::PumpStatus,
     },
@@ -32,12 +39,12 @@ use smithay::{
 };
 use tracing::{info, warn};
 
-use crate::utils::process;
-
 pub struct WinitData {
     backend: WinitGraphicsBackend<GlowRenderer>,
     damage_tracker: OutputDamageTracker,
     dmabuf_state: (DmabufState, DmabufGlobal, Option<DmabufFeedback>),
+    background: Option<TextureBuffer<GlesTexture>>,
+    cursor_textures: CursorTextures,
 }
 
 impl DmabufHandler for MagmaState<WinitData> {
@@ -72,8 +79,15 @@ impl Backend for WinitData {
     }
 }
 use crate::{
-    state::{Backend, CalloopData, MagmaState, CONFIG},
-    utils::render::{border::BorderShader, init_shaders, CustomRenderElements},
+    handlers::presentation::refresh_interval,
+    state::{Backend, CalloopData, MagmaState},
+    utils::render::{
+        background::{background_texture, clear_color},
+        border::BorderShader,
+        cursor::{cursor_elements, CursorTextures},
+        dnd_icon_elements, init_shaders, lock_elements, output_size, override_redirect_elements,
+        CustomRenderElements, LOCKED_COLOR,
+    },
 };
 
 pub fn init_winit() {
@@ -159,6 +173,8 @@ pub fn init_winit() {
         backend,
         damage_tracker: damage_tracked_renderer,
         dmabuf_state,
+        background: None,
+        cursor_textures: CursorTextures::default(),
     };
     let display_handle: DisplayHandle = display.handle().clone();
     let state = MagmaState::new(
@@ -175,6 +191,17 @@ pub fn init_winit() {
 
     let state = &mut data.state;
     init_shaders(state.backend_data.backend.renderer());
+    // the cursor is drawn by us, like on the udev backend
+    state
+        .backend_data
+        .backend
+        .window()
+        .set_cursor_visible(false);
+    state.backend_data.background = background_texture(
+        state.backend_data.backend.renderer(),
+        &output,
+        &mut state.background_images,
+    );
     // map output to every workspace
     for workspace in state.workspaces.iter() {
         workspace.add_output(output.clone());
@@ -194,9 +221,7 @@ pub fn init_winit() {
         })
         .unwrap();
 
-    for command in &CONFIG.autostart {
-        process::spawn(command);
-    }
+    data.state.start_xwayland();
 
     event_loop
         .run(None, &mut data, move |_| {
@@ -225,6 +250,11 @@ pub fn winit_dispatch(
                 None,
             );
             layer_map_for_output(output).arrange();
+            state.backend_data.background = background_texture(
+                state.backend_data.backend.renderer(),
+                output,
+                &mut state.background_images,
+            );
         }
         WinitEvent::Input(event) => state.process_input_event(event),
         _ => (),
@@ -247,6 +277,16 @@ pub fn winit_dispatch(
     let mut renderelements: Vec<CustomRenderElements<_>> = vec![];
     let workspace = state.workspaces.current_mut();
     let output = workspace.outputs().next().unwrap();
+    let scale = Scale::from(output.current_scale().fractional_scale());
+    renderelements.extend(cursor_elements(
+        winitdata.backend.renderer(),
+        &mut state.cursor_theme,
+        &mut winitdata.cursor_textures,
+        &state.cursor_status,
+        state.pointer_location,
+        state.start_time.elapsed(),
+        scale,
+    ));
     #[cfg(feature = "debug")]
     if state.debug.active {
         renderelements.push(
@@ -255,103 +295,136 @@ pub fn winit_dispatch(
                 .global_ui(
                     None,
                     output,
+                    None,
                     &state.seat,
                     winitdata.backend.renderer(),
-                    Rectangle::from_loc_and_size(
-                        (0, 0),
-                        output.current_mode().unwrap().size.to_logical(1),
-                    ),
-                    1.0,
+                    Rectangle::from_loc_and_size((0, 0), output_size(output)),
+                    scale.x,
                     0.8,
                 )
                 .unwrap()
                 .into(),
         );
     }
-    let layer_map = layer_map_for_output(output);
-    let (lower, upper): (Vec<&LayerSurface>, Vec<&LayerSurface>) = layer_map
-        .layers()
-        .rev()
-        .partition(|s| matches!(s.layer(), Layer::Background | Layer::Bottom));
-
-    renderelements.extend(
-        upper
-            .into_iter()
-            .filter_map(|surface| {
-                layer_map
-                    .layer_geometry(surface)
-                    .map(|geo| (geo.loc, surface))
-            })
-            .flat_map(|(loc, surface)| {
-                AsRenderElements::<GlowRenderer>::render_elements::<CustomRenderElements<_>>(
-                    surface,
-                    winitdata.backend.renderer(),
-                    loc.to_physical_precise_round(1),
-                    Scale::from(1.0),
-                    1.0,
-                )
-            }),
-    );
+    let clear = if let Some(lock) = &state.session_lock {
+        renderelements.extend(lock_elements(lock, output, winitdata.backend.renderer()));
+        LOCKED_COLOR
+    } else {
+        renderelements.extend(dnd_icon_elements(
+            state.dnd_icon.as_ref(),
+            state.pointer_location,
+            winitdata.backend.renderer(),
+            scale,
+        ));
+        renderelements.extend(override_redirect_elements(
+            &state.override_redirect,
+            winitdata.backend.renderer(),
+            scale,
+        ));
+        let layer_map = layer_map_for_output(output);
+        let (lower, upper): (Vec<&LayerSurface>, Vec<&LayerSurface>) = layer_map
+            .layers()
+            .rev()
+            .partition(|s| matches!(s.layer(), Layer::Background | Layer::Bottom));
+
+        renderelements.extend(
+            upper
+                .into_iter()
+                .filter_map(|surface| {
+                    layer_map
+                        .layer_geometry(surface)
+                        .map(|geo| (geo.loc, surface))
+                })
+                .flat_map(|(loc, surface)| {
+                    AsRenderElements::<GlowRenderer>::render_elements::<CustomRenderElements<_>>(
+                        surface,
+                        winitdata.backend.renderer(),
+                        loc.to_physical_precise_round(scale),
+                        scale,
+                        1.0,
+                    )
+                }),
+        );
 
-    renderelements.extend(workspace.render_elements(winitdata.backend.renderer()));
-
-    renderelements.extend(
-        lower
-            .into_iter()
-            .filter_map(|surface| {
-                layer_map
-                    .layer_geometry(surface)
-                    .map(|geo| (geo.loc, surface))
-            })
-            .flat_map(|(loc, surface)| {
-                AsRenderElements::<GlowRenderer>::render_elements::<CustomRenderElements<_>>(
-                    surface,
-                    winitdata.backend.renderer(),
-                    loc.to_physical_precise_round(1),
-                    Scale::from(1.0),
-                    1.0,
-                )
-            }),
-    );
+        renderelements.extend(workspace.render_elements(
+            winitdata.backend.renderer(),
+            output,
+            scale,
+        ));
+
+        renderelements.extend(
+            lower
+                .into_iter()
+                .filter_map(|surface| {
+                    layer_map
+                        .layer_geometry(surface)
+                        .map(|geo| (geo.loc, surface))
+                })
+                .flat_map(|(loc, surface)| {
+                    AsRenderElements::<GlowRenderer>::render_elements::<CustomRenderElements<_>>(
+                        surface,
+                        winitdata.backend.renderer(),
+                        loc.to_physical_precise_round(scale),
+                        scale,
+                        1.0,
+                    )
+                }),
+        );
+
+        if let Some(background) = &winitdata.background {
+            renderelements.push(CustomRenderElements::from(
+                TextureRenderElement::from_texture_buffer(
+                    (0.0, 0.0),
+                    background,
+                    None,
+                    None,
+                    // the texture has the transformed size of the mode
+                    Some(output_size(output)),
+                    Kind::Unspecified,
+                ),
+            ));
+        }
+        clear_color(output)
+    };
     #[cfg(feature = "debug")]
     state.debug.fps.elements();
     winitdata.backend.bind().unwrap();
-    winitdata
+    let render_res = winitdata
         .damage_tracker
-        .render_output(
-            winitdata.backend.renderer(),
-            0,
-            &renderelements,
-            [0.1, 0.1, 0.1, 1.0],
-        )
+        .render_output(winitdata.backend.renderer(), 0, &renderelements, clear)
         .unwrap();
+    let rendered = render_res.damage.is_some();
+    let states = render_res.states;
     #[cfg(feature = "debug")]
     state.debug.fps.render();
     winitdata.backend.submit(Some(&[damage])).unwrap();
     #[cfg(feature = "debug")]
     state.debug.fps.displayed();
 
-    workspace.windows().for_each(|window| {
-        window.send_frame(
-            output,
-            state.start_time.elapsed(),
-            Some(Duration::ZERO),
-            |_, _| Some(output.clone()),
-        )
-    });
+    workspace.windows().for_each(|e| e.refresh());
+    BorderShader::cleanup(winitdata.backend.renderer());
 
-    drop(layer_map);
-    for layer in layer_map_for_output(output).layers() {
-        layer.send_frame(
-            output,
-            state.start_time.elapsed(),
-            Some(Duration::ZERO),
-            |_, _| Some(output.clone()),
-        );
+    let output = output.clone();
+    // the host compositor shows the frame once it is submitted
+    if let Some(lock) = state.session_lock.as_mut() {
+        lock.frame_presented(&output);
     }
-
-    workspace.windows().for_each(|e| e.refresh());
+    state.confirm_session_lock();
+    state.update_primary_scanout_output(&output, &states);
+    if rendered {
+        // the host compositor doesn't tell when the frame is shown, so report it as shown now
+        state
+            .take_presentation_feedback(&output, &states)
+            .presented(
+                state.clock.now(),
+                refresh_interval(&output),
+                0,
+                wp_presentation_feedback::Kind::Vsync,
+            );
+    }
+    state.send_frames(&output);
+    state.refresh_foreign_toplevels();
+    state.send_preferred_scales();
     data.display_handle.flush_clients().unwrap();
     state.popup_manager.cleanup();
-    BorderShader::cleanup(winitdata.backend.renderer());
 }
@@ -36,10 +36,57 @@ pub struct Config {
 
     #[serde(default = "default_borders")]
     pub borders: Borders,
+
+    /// Background per output name, `"*"` applies to all other outputs
+    #[serde(default = "default_background", alias = "backgrounds")]
+    pub background: IndexMap<String, Background>,
+
+    #[serde(default = "default_cursor")]
+    pub cursor: CursorConfig,
+
+    #[serde(default = "default_xwayland")]
+    pub xwayland: bool,
+
+    #[serde(default = "default_title_bars")]
+    pub title_bars: TitleBars,
+
+    /// Rules applied to windows, the first matching rule setting an option wins
+    #[serde(default = "default_window_rules")]
+    pub window_rules: Vec<WindowRule>,
+
+    #[serde(default = "default_idle")]
+    pub idle: Vec<IdleConfig>,
+
+    /// Keep the clipboard after the client it was copied from exits
+    #[serde(default = "default_clipboard_persistence")]
+    pub clipboard_persistence: bool,
+}
+
+impl Config {
+    pub fn background(&self, output: &str) -> Option<&Background> {
+        self.background
+            .get(output)
+            .or_else(|| self.background.get("*"))
+    }
+
+    pub fn decoration(&self, app_id: &str, title: &str) -> Option<Decoration> {
+        self.window_rules
+            .iter()
+            .filter(|rule| rule.matches(app_id, title))
+            .find_map(|rule| rule.decoration)
+    }
 }
 
+/// Mode of an output: size, refresh rate, an optional modeline
+/// (`"cvt"` or X11 style timings) used if the mode is not advertised by the monitor
+/// and when to use adaptive sync.
 #[derive(Debug, Deserialize, Clone, Serialize)]
-pub struct OutputConfig((i32, i32), Option<u32>);
+pub struct OutputConfig(
+    (i32, i32),
+    Option<u32>,
+    #[serde(default)] Option<String>,
+    #[serde(default)] Vrr,
+);
 
 impl OutputConfig {
     pub fn mode_size(&self) -> Size<i32, Physical> {
@@ -49,6 +96,24 @@ impl OutputConfig {
     pub fn mode_refresh(&self) -> u32 {
         self.1.unwrap_or(60) * 1000
     }
+
+    pub fn modeline(&self) -> Option<&str> {
+        self.2.as_deref()
+    }
+
+    pub fn vrr(&self) -> Vrr {
+        self.3
+    }
+}
+
+/// When an output uses adaptive sync (VRR), if the monitor supports it
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Vrr {
+    #[default]
+    Off,
+    On,
+    /// Only while a window covers the whole output, e.g. a game
+    OnlyFullscreen,
 }
 
 pub fn generate_config() -> PathBuf {
@@ -124,6 +189,13 @@ pub fn generate_config() -> PathBuf {
         autostart: default_autostart(),
         outputs: default_outputs(),
         borders: default_borders(),
+        background: default_background(),
+        cursor: default_cursor(),
+        xwayland: default_xwayland(),
+        title_bars: default_title_bars(),
+        window_rules: default_window_rules(),
+        idle: default_idle(),
+        clipboard_persistence: default_clipboard_persistence(),
     };
     let pretty = PrettyConfig::new().compact_arrays(true).depth_limit(2);
     let ron = ron::ser::to_string_pretty(&default_config, pretty).unwrap();
@@ -173,6 +245,10 @@ fn default_autostart() -> Vec<String> {
     vec![]
 }
 
+fn default_clipboard_persistence() -> bool {
+    false
+}
+
 fn default_outputs() -> IndexMap<String, OutputConfig> {
     IndexMap::new()
 }
@@ -184,9 +260,45 @@ fn default_borders() -> Borders {
         end_color: Some([0.580, 0.921, 0.921]),
         radius: 8.0,
         gradient_angle: 0.0,
+        urgent_color: default_urgent_color(),
+    }
+}
+
+fn default_urgent_color() -> [f32; 3] {
+    [0.937, 0.325, 0.314]
+}
+
+fn default_background() -> IndexMap<String, Background> {
+    IndexMap::new()
+}
+
+fn default_cursor() -> CursorConfig {
+    CursorConfig {
+        theme: None,
+        size: None,
     }
 }
 
+fn default_xwayland() -> bool {
+    true
+}
+
+fn default_title_bars() -> TitleBars {
+    TitleBars {
+        height: 0,
+        color: [0.2, 0.2, 0.2],
+        text_color: [0.880, 1.0, 1.0],
+    }
+}
+
+fn default_window_rules() -> Vec<WindowRule> {
+    vec![]
+}
+
+fn default_idle() -> Vec<IdleConfig> {
+    vec![]
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub enum KeyModifier {
     Ctrl,
@@ -229,6 +341,10 @@ pub enum Action {
     ToggleWindowFloating,
     VTSwitch(i32),
     Spawn(String),
+    /// Turn the output with this name off or back on
+    ToggleOutputPower(String),
+    /// Switch to a window demanding attention
+    FocusUrgent,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -242,4 +358,100 @@ pub struct Borders {
     pub end_color: Option<[f32; 3]>,
     pub radius: f32,
     pub gradient_angle: f32,
+    /// Colour of the border of windows demanding attention
+    #[serde(default = "default_urgent_color")]
+    #[serde(deserialize_with = "deserialize_StartColour")]
+    #[serde(serialize_with = "serialize_StartColour")]
+    pub urgent_color: [f32; 3],
+}
+
+/// What is drawn behind all windows and layer surfaces of an output
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub enum Background {
+    Color(
+        #[serde(deserialize_with = "deserialize_StartColour")]
+        #[serde(serialize_with = "serialize_StartColour")]
+        [f32; 3],
+    ),
+    Image {
+        path: PathBuf,
+        #[serde(default)]
+        mode: BackgroundMode,
+    },
+}
+
+/// How a background image is scaled to the size of the output
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum BackgroundMode {
+    /// Scale to cover the whole output, cropping the image
+    #[default]
+    Fill,
+    /// Scale to fit inside the output, keeping the aspect ratio
+    Fit,
+    /// Draw at its original size in the middle of the output
+    Center,
+    /// Repeat at its original size from the top left corner
+    Tile,
+}
+
+/// XCursor theme and size, falling back to `XCURSOR_THEME` and `XCURSOR_SIZE`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CursorConfig {
+    #[serde(default)]
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub size: Option<u32>,
+}
+
+/// Title bars drawn above server side decorated windows, disabled with a `height` of 0
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TitleBars {
+    pub height: u8,
+    #[serde(deserialize_with = "deserialize_StartColour")]
+    #[serde(serialize_with = "serialize_StartColour")]
+    pub color: [f32; 3],
+    #[serde(deserialize_with = "deserialize_StartColour")]
+    #[serde(serialize_with = "serialize_StartColour")]
+    pub text_color: [f32; 3],
+}
+
+/// Matches windows by their exact `app_id` (the class for X11 windows)
+/// and a part of their `title`, leaving a field out matches every window.
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct WindowRule {
+    #[serde(default)]
+    pub app_id: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub decoration: Option<Decoration>,
+}
+
+impl WindowRule {
+    pub fn matches(&self, app_id: &str, title: &str) -> bool {
+        self.app_id.as_deref().map_or(true, |a| a == app_id)
+            && self.title.as_deref().map_or(true, |t| title.contains(t))
+    }
+}
+
+/// Who draws the decorations of a window, overriding what the client asks for
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Decoration {
+    Client,
+    Server,
+}
+
+/// Action taken once there was no input for `timeout` seconds,
+/// unless a visible surface inhibits idling
+#[derive(Debug, Deserialize, Clone, Serialize)]
+pub struct IdleConfig {
+    pub timeout: u64,
+    pub action: IdleAction,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Serialize)]
+pub enum IdleAction {
+    Spawn(String),
+    /// Turn all outputs off until the next input
+    PowerOff,
 }
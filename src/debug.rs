@@ -14,6 +14,7 @@ use smithay::{
             glow::GlowRenderer,
         },
     },
+    desktop::WindowSurface,
     input::{keyboard::xkb, Seat},
     output::Output,
     reexports::wayland_server::Resource,
@@ -44,6 +45,8 @@ impl MagmaDebug {
         &mut self,
         gpu: Option<&DrmNode>,
         output: &Output,
+        // whether the last frame was a client buffer scanned out directly, if known
+        direct_scanout: Option<bool>,
         seat: &Seat<MagmaState<BackendData>>,
         renderer: &mut GlowRenderer,
         area: Rectangle<i32, Logical>,
@@ -178,6 +181,17 @@ impl MagmaDebug {
                                 "Refresh Rate: {}hz",
                                 output.current_mode().unwrap().refresh / 1000
                             ));
+                            if let Some(direct_scanout) = direct_scanout {
+                                let (text, color) = if direct_scanout {
+                                    ("active", Color32::GREEN)
+                                } else {
+                                    ("inactive", Color32::GRAY)
+                                };
+                                ui.label(
+                                    egui::RichText::new(format!("Direct scanout: {}", text))
+                                        .color(color),
+                                );
+                            }
                             ui.separator();
                         }
                         ui.label(egui::RichText::new(format!("\t{}", seat.name())).strong());
@@ -269,25 +283,33 @@ impl MagmaDebug {
 fn format_focus(focus: Option<FocusTarget>) -> String {
     if let Some(focus) = focus {
         match focus {
-            FocusTarget::Window(w) => format!(
-                "Window {} ({})",
-                w.toplevel().unwrap().wl_surface().id().protocol_id(),
-                with_states(w.toplevel().unwrap().wl_surface(), |states| {
-                    states
-                        .data_map
-                        .get::<XdgToplevelSurfaceData>()
-                        .unwrap()
-                        .lock()
-                        .unwrap()
-                        .title
-                        .clone()
-                        .unwrap_or_default()
-                })
-            ),
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(toplevel) => format!(
+                    "Window {} ({})",
+                    toplevel.wl_surface().id().protocol_id(),
+                    with_states(toplevel.wl_surface(), |states| {
+                        states
+                            .data_map
+                            .get::<XdgToplevelSurfaceData>()
+                            .unwrap()
+                            .lock()
+                            .unwrap()
+                            .title
+                            .clone()
+                            .unwrap_or_default()
+                    })
+                ),
+                WindowSurface::X11(surface) => {
+                    format!("X11 Window {} ({})", surface.window_id(), surface.title())
+                }
+            },
             FocusTarget::LayerSurface(l) => {
                 format!("LayerSurface {}", l.wl_surface().id().protocol_id())
             }
             FocusTarget::Popup(p) => format!("Popup {}", p.wl_surface().id().protocol_id()),
+            FocusTarget::LockSurface(l) => {
+                format!("LockSurface {}", l.wl_surface().id().protocol_id())
+            }
         }
     } else {
         "None".to_string()
@@ -0,0 +1,263 @@
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::fd::OwnedFd,
+    sync::{atomic::Ordering, Arc},
+};
+
+use smithay::{
+    reexports::{
+        calloop::{generic::Generic, Interest, Mode, PostAction},
+        rustix::{
+            fs::{fcntl_setfl, OFlags},
+            pipe::{pipe_with, PipeFlags},
+        },
+        wayland_server::Client,
+    },
+    wayland::selection::{
+        data_device::{request_data_device_client_selection, set_data_device_selection},
+        SelectionTarget,
+    },
+};
+use tracing::{debug, warn};
+
+use crate::state::{Backend, ClientState, MagmaState};
+
+/// Largest selection kept after its client went away, over all of its mime types.
+const MAX_PERSISTED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Selections offered by the compositor instead of a wayland client.
+#[derive(Debug, Clone)]
+pub enum CompositorSelection {
+    /// The selection of an X11 client, read through XWayland
+    Xwayland,
+    /// A copy of the clipboard of a client that went away
+    Persisted(Arc<Vec<(String, Vec<u8>)>>),
+}
+
+/// Copy of the last clipboard selection set by a client.
+#[derive(Default)]
+pub struct PersistedClipboard {
+    // bumped for every new selection, so reads of older ones are dropped
+    serial: u64,
+    // client the selection was copied from
+    owner: Option<Client>,
+    contents: Vec<(String, Vec<u8>)>,
+    // bytes read so far, including the mime types still being read
+    size: usize,
+    // mime types still being read
+    pending: usize,
+    // the owner went away before all mime types were read
+    restore: bool,
+}
+
+impl PersistedClipboard {
+    fn reset(&mut self) {
+        self.serial += 1;
+        self.owner = None;
+        self.contents.clear();
+        self.size = 0;
+        self.pending = 0;
+        self.restore = false;
+    }
+}
+
+// other mime types are mostly application specific and can be large
+fn persisted_mime_type(mime_type: &str) -> bool {
+    mime_type.starts_with("text/") || mime_type.starts_with("image/")
+}
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Start copying the new clipboard selection of a client, see [`Self::clipboard_cleared`].
+    pub fn persist_clipboard(&mut self, mime_types: Vec<String>, owner: Option<Client>) {
+        let clipboard = &mut self.persisted_clipboard;
+        clipboard.reset();
+        clipboard.owner = owner;
+        let serial = clipboard.serial;
+        // the selection only becomes current after the handler returned
+        self.loop_handle.insert_idle(move |data| {
+            for mime_type in mime_types {
+                if persisted_mime_type(&mime_type) {
+                    data.state.read_clipboard(serial, mime_type);
+                }
+            }
+        });
+    }
+
+    /// The clipboard selection is gone, offer the copy instead if that is because its
+    /// client went away, or forget it if the clipboard was cleared on purpose.
+    pub fn clipboard_cleared(&mut self) {
+        let serial = self.persisted_clipboard.serial;
+        // clients are only told they disconnected after their sources were destroyed
+        self.loop_handle.insert_idle(move |data| {
+            let state = &mut data.state;
+            let clipboard = &mut state.persisted_clipboard;
+            if serial != clipboard.serial {
+                return;
+            }
+            let owner_gone = clipboard
+                .owner
+                .as_ref()
+                .and_then(|owner| owner.get_data::<ClientState>())
+                .is_some_and(|owner| owner.disconnected.load(Ordering::Relaxed));
+            if !owner_gone {
+                clipboard.reset();
+            } else if clipboard.pending > 0 {
+                debug!("Restoring the clipboard once it was copied");
+                clipboard.restore = true;
+            } else {
+                state.restore_clipboard();
+            }
+        });
+    }
+
+    fn restore_clipboard(&mut self) {
+        let clipboard = &self.persisted_clipboard;
+        if clipboard.contents.is_empty() {
+            debug!("Nothing of the clipboard was kept to restore it");
+            return;
+        }
+        let mime_types: Vec<String> = clipboard.contents.iter().map(|(m, _)| m.clone()).collect();
+        set_data_device_selection(
+            &self.dh,
+            &self.seat,
+            mime_types.clone(),
+            CompositorSelection::Persisted(Arc::new(clipboard.contents.clone())),
+        );
+        // clearing the restored selection forgets it
+        self.persisted_clipboard.owner = None;
+        if let Some(xwm) = self.xwm.as_mut() {
+            if let Err(err) = xwm.new_selection(SelectionTarget::Clipboard, Some(mime_types)) {
+                warn!(?err, "Failed to set XWayland selection");
+            }
+        }
+    }
+
+    /// Write the persisted content for `mime_type` to a client, without blocking the compositor.
+    pub fn send_persisted(
+        &self,
+        contents: &Arc<Vec<(String, Vec<u8>)>>,
+        mime_type: &str,
+        fd: OwnedFd,
+    ) {
+        let Some(index) = contents.iter().position(|(m, _)| m == mime_type) else {
+            return;
+        };
+        if let Err(err) = fcntl_setfl(&fd, OFlags::NONBLOCK) {
+            warn!("Failed to send the clipboard without blocking: {}", err);
+            return;
+        }
+
+        let contents = contents.clone();
+        let mut written = 0;
+        let source = Generic::new(File::from(fd), Interest::WRITE, Mode::Level);
+        let inserted = self.loop_handle.insert_source(source, move |_, file, _| {
+            let content = &contents[index].1;
+            loop {
+                if written == content.len() {
+                    return Ok(PostAction::Remove);
+                }
+                match unsafe { file.get_mut() }.write(&content[written..]) {
+                    Ok(n) => written += n,
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(err) => {
+                        debug!("Failed to send the clipboard: {}", err);
+                        return Ok(PostAction::Remove);
+                    }
+                }
+            }
+        });
+        if let Err(err) = inserted {
+            warn!("Failed to send the clipboard: {}", err);
+        }
+    }
+
+    fn read_clipboard(&mut self, serial: u64, mime_type: String) {
+        if serial != self.persisted_clipboard.serial {
+            return;
+        }
+        let (read, write) = match pipe_with(PipeFlags::CLOEXEC) {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                warn!("Failed to create a pipe for the clipboard: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = request_data_device_client_selection(&self.seat, mime_type.clone(), write)
+        {
+            debug!(?err, "Failed to copy the clipboard as {}", mime_type);
+            return;
+        }
+        if let Err(err) = fcntl_setfl(&read, OFlags::NONBLOCK) {
+            warn!("Failed to read the clipboard without blocking: {}", err);
+            return;
+        }
+
+        self.persisted_clipboard.pending += 1;
+        let mut content = Vec::new();
+        let mut chunk = [0; 4096];
+        let source = Generic::new(File::from(read), Interest::READ, Mode::Level);
+        let inserted = self
+            .loop_handle
+            .insert_source(source, move |_, file, data| loop {
+                match unsafe { file.get_mut() }.read(&mut chunk) {
+                    Ok(0) => {
+                        let content = std::mem::take(&mut content);
+                        data.state
+                            .clipboard_read(serial, Some((mime_type.clone(), content)));
+                        return Ok(PostAction::Remove);
+                    }
+                    Ok(n) => {
+                        let clipboard = &mut data.state.persisted_clipboard;
+                        if serial != clipboard.serial {
+                            return Ok(PostAction::Remove);
+                        }
+                        if clipboard.size + n > MAX_PERSISTED_SIZE {
+                            debug!(
+                                "Not keeping the clipboard as {}, the selection is too large",
+                                mime_type
+                            );
+                            clipboard.size -= content.len();
+                            data.state.clipboard_read(serial, None);
+                            return Ok(PostAction::Remove);
+                        }
+                        clipboard.size += n;
+                        content.extend_from_slice(&chunk[..n]);
+                    }
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                        return Ok(PostAction::Continue)
+                    }
+                    Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(err) => {
+                        warn!("Failed to read the clipboard as {}: {}", mime_type, err);
+                        let clipboard = &mut data.state.persisted_clipboard;
+                        if serial == clipboard.serial {
+                            clipboard.size -= content.len();
+                        }
+                        data.state.clipboard_read(serial, None);
+                        return Ok(PostAction::Remove);
+                    }
+                }
+            });
+        if let Err(err) = inserted {
+            warn!("Failed to read the clipboard: {}", err);
+            self.persisted_clipboard.pending -= 1;
+        }
+    }
+
+    fn clipboard_read(&mut self, serial: u64, content: Option<(String, Vec<u8>)>) {
+        let clipboard = &mut self.persisted_clipboard;
+        if serial != clipboard.serial {
+            return;
+        }
+        clipboard.pending -= 1;
+        clipboard.contents.extend(content);
+        if clipboard.pending == 0 && clipboard.restore {
+            clipboard.restore = false;
+            self.restore_clipboard();
+        }
+    }
+}
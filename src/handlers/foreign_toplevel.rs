@@ -0,0 +1,90 @@
+use smithay::desktop::Window;
+use tracing::debug;
+
+use crate::{
+    delegate_foreign_toplevel, delegate_foreign_toplevel_list,
+    protocols::{
+        foreign_toplevel::{ForeignToplevelHandler, ForeignToplevelManagerState, ToplevelInfo},
+        foreign_toplevel_list::{ForeignToplevelListHandler, ForeignToplevelListState},
+    },
+    state::{Backend, MagmaState},
+    utils::{focus::FocusTarget, window::WindowExt},
+};
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Send what changed about the windows to taskbars, once per event loop iteration.
+    pub fn refresh_foreign_toplevels(&mut self) {
+        let focus = self.seat.get_keyboard().unwrap().current_focus();
+        let current = self.workspaces.current();
+        let output = current.outputs().next().cloned();
+        let windows: Vec<(Window, ToplevelInfo)> = self
+            .workspaces
+            .all_windows()
+            .map(|window| {
+                let info = ToplevelInfo {
+                    title: window.title(),
+                    app_id: window.app_id(),
+                    activated: focus.as_ref() == Some(&FocusTarget::Window(window.clone())),
+                    urgent: self.workspaces.is_urgent(&window),
+                    output: output.clone().filter(|_| current.contains_window(&window)),
+                };
+                (window.clone(), info)
+            })
+            .collect();
+
+        self.foreign_toplevel_manager_state
+            .retain(|w| windows.iter().any(|(window, _)| window == w));
+        self.foreign_toplevel_list_state
+            .retain(|w| windows.iter().any(|(window, _)| window == w));
+        for (window, info) in windows {
+            self.foreign_toplevel_list_state.update::<Self>(
+                &window,
+                info.title.clone(),
+                info.app_id.clone(),
+            );
+            self.foreign_toplevel_manager_state
+                .update::<Self>(&window, info);
+        }
+    }
+}
+
+impl<BackendData: Backend> ForeignToplevelHandler for MagmaState<BackendData> {
+    fn foreign_toplevel_manager_state(&mut self) -> &mut ForeignToplevelManagerState {
+        &mut self.foreign_toplevel_manager_state
+    }
+
+    fn activate(&mut self, window: Window) {
+        self.focus_window(&window);
+    }
+
+    fn close(&mut self, window: Window) {
+        window.close();
+    }
+
+    // tiled windows can't be maximized, minimized or made fullscreen (yet),
+    // so their state simply stays as it is
+    fn set_maximized(&mut self, window: Window, maximized: bool) {
+        debug!(title = %window.title(), maximized, "Ignoring maximize request");
+    }
+
+    fn set_minimized(&mut self, window: Window, minimized: bool) {
+        debug!(title = %window.title(), minimized, "Ignoring minimize request");
+    }
+
+    fn set_fullscreen(&mut self, window: Window, fullscreen: bool) {
+        debug!(
+            title = %window.title(),
+            fullscreen, "Ignoring fullscreen request"
+        );
+    }
+}
+
+delegate_foreign_toplevel!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
+
+impl<BackendData: Backend> ForeignToplevelListHandler for MagmaState<BackendData> {
+    fn foreign_toplevel_list_state(&mut self) -> &mut ForeignToplevelListState {
+        &mut self.foreign_toplevel_list_state
+    }
+}
+
+delegate_foreign_toplevel_list!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
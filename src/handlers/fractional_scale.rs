@@ -0,0 +1,79 @@
+use smithay::{
+    delegate_fractional_scale, delegate_viewporter,
+    desktop::{layer_map_for_output, utils::with_surfaces_surface_tree, WindowSurfaceType},
+    output::Output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::{
+        compositor::{get_parent, with_states, SurfaceData},
+        fractional_scale::{with_fractional_scale, FractionalScaleHandler},
+    },
+};
+
+use crate::state::{Backend, MagmaState};
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Tell every visible surface the scale of its output, once per event loop iteration.
+    ///
+    /// Clients are only sent a new preferred scale when it changed.
+    pub fn send_preferred_scales(&self) {
+        let workspace = self.workspaces.current();
+        // windows are laid out on the first output of the workspace
+        if let Some(output) = workspace.outputs().next() {
+            let scale = output.current_scale().fractional_scale();
+            for window in workspace.windows() {
+                window.with_surfaces(|_, states| send_scale(states, scale));
+            }
+            for window in &self.override_redirect {
+                window.with_surfaces(|_, states| send_scale(states, scale));
+            }
+        }
+        for output in workspace.outputs() {
+            let scale = output.current_scale().fractional_scale();
+            for layer in layer_map_for_output(output).layers() {
+                layer.with_surfaces(|_, states| send_scale(states, scale));
+            }
+            if let Some(lock) = self.session_lock.as_ref().and_then(|l| l.surface(output)) {
+                with_surfaces_surface_tree(lock.wl_surface(), |_, states| {
+                    send_scale(states, scale)
+                });
+            }
+        }
+    }
+
+    // the output a toplevel surface is shown on
+    fn output_for_surface(&self, root: &WlSurface) -> Option<Output> {
+        let workspace = self.workspaces.current();
+        workspace
+            .outputs()
+            .find(|o| {
+                layer_map_for_output(o)
+                    .layer_for_surface(root, WindowSurfaceType::TOPLEVEL)
+                    .is_some()
+            })
+            .or_else(|| workspace.outputs().next())
+            .cloned()
+    }
+}
+
+fn send_scale(states: &SurfaceData, scale: f64) {
+    with_fractional_scale(states, |fractional_scale| {
+        fractional_scale.set_preferred_scale(scale);
+    });
+}
+
+impl<BackendData: Backend> FractionalScaleHandler for MagmaState<BackendData> {
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+        let scale = self
+            .output_for_surface(&root)
+            .map_or(1.0, |o| o.current_scale().fractional_scale());
+        with_states(&surface, |states| send_scale(states, scale));
+    }
+}
+
+delegate_fractional_scale!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
+
+delegate_viewporter!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
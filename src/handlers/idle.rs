@@ -0,0 +1,159 @@
+use std::time::{Duration, Instant};
+
+use smithay::{
+    delegate_idle_inhibit, delegate_idle_notify,
+    desktop::{layer_map_for_output, WindowSurfaceType},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::IsAlive,
+    wayland::{
+        compositor::get_parent,
+        idle_inhibit::IdleInhibitHandler,
+        idle_notify::{IdleNotifierHandler, IdleNotifierState},
+        seat::WaylandFocus,
+    },
+};
+use tracing::info;
+
+use crate::{
+    config::IdleAction,
+    state::{Backend, MagmaState, CONFIG},
+    utils::process,
+};
+
+/// How often the configured idle timeouts are checked.
+pub const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Idle {
+    last_activity: Instant,
+    // which of the configured idle actions already ran since the last input
+    fired: Vec<bool>,
+    inhibitors: Vec<WlSurface>,
+    inhibited: bool,
+}
+
+impl Default for Idle {
+    fn default() -> Self {
+        Self {
+            last_activity: Instant::now(),
+            fired: vec![false; CONFIG.idle.len()],
+            inhibitors: Vec::new(),
+            inhibited: false,
+        }
+    }
+}
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Reset the idle timers and wake outputs that were turned off, called for input events.
+    ///
+    /// Returns whether an output was turned back on.
+    pub fn notify_activity(&mut self) -> bool {
+        self.idle_notifier_state.notify_activity(&self.seat);
+        self.idle.last_activity = Instant::now();
+        self.idle.fired.iter_mut().for_each(|fired| *fired = false);
+        self.set_outputs_power(true)
+    }
+
+    /// Run the configured idle actions whose timeout passed.
+    pub fn check_idle(&mut self) {
+        self.update_idle_inhibit();
+        if self.idle.inhibited {
+            // count from the end of the inhibition
+            self.idle.last_activity = Instant::now();
+            return;
+        }
+
+        let elapsed = self.idle.last_activity.elapsed();
+        for (i, idle) in CONFIG.idle.iter().enumerate() {
+            if self.idle.fired[i] || elapsed < Duration::from_secs(idle.timeout) {
+                continue;
+            }
+            self.idle.fired[i] = true;
+            info!("Idle for {}s, running {:?}", idle.timeout, idle.action);
+            match &idle.action {
+                IdleAction::Spawn(command) => {
+                    process::spawn(command);
+                }
+                IdleAction::PowerOff => {
+                    self.set_outputs_power(false);
+                }
+            }
+        }
+    }
+
+    // returns whether the power of any output changed
+    fn set_outputs_power(&mut self, on: bool) -> bool {
+        let outputs: Vec<_> = self
+            .workspaces
+            .current()
+            .outputs()
+            .filter(|o| self.backend_data.is_output_powered(o) != on)
+            .cloned()
+            .collect();
+        for output in &outputs {
+            BackendData::set_output_power(self, output, on);
+        }
+        !outputs.is_empty()
+    }
+
+    // inhibitors only count while their surface can be seen
+    fn update_idle_inhibit(&mut self) {
+        self.idle.inhibitors.retain(|s| s.alive());
+        let inhibited = self
+            .idle
+            .inhibitors
+            .iter()
+            .any(|surface| self.is_surface_visible(surface));
+        if inhibited != self.idle.inhibited {
+            self.idle.inhibited = inhibited;
+            self.idle_notifier_state.set_is_inhibited(inhibited);
+        }
+    }
+
+    fn is_surface_visible(&self, surface: &WlSurface) -> bool {
+        let mut root = surface.clone();
+        while let Some(parent) = get_parent(&root) {
+            root = parent;
+        }
+        let workspace = self.workspaces.current();
+        if let Some(lock) = &self.session_lock {
+            return workspace
+                .outputs()
+                .filter_map(|o| lock.surface(o))
+                .any(|s| s.wl_surface() == &root);
+        }
+        workspace
+            .windows()
+            .any(|w| w.wl_surface().as_ref() == Some(&root))
+            || self
+                .override_redirect
+                .iter()
+                .any(|w| w.wl_surface().as_ref() == Some(&root))
+            || workspace.outputs().any(|o| {
+                layer_map_for_output(o)
+                    .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                    .is_some()
+            })
+    }
+}
+
+impl<BackendData: Backend> IdleNotifierHandler for MagmaState<BackendData> {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState<Self> {
+        &mut self.idle_notifier_state
+    }
+}
+
+delegate_idle_notify!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
+
+impl<BackendData: Backend> IdleInhibitHandler for MagmaState<BackendData> {
+    fn inhibit(&mut self, surface: WlSurface) {
+        self.idle.inhibitors.push(surface);
+        self.update_idle_inhibit();
+    }
+
+    fn uninhibit(&mut self, surface: WlSurface) {
+        self.idle.inhibitors.retain(|s| s != &surface);
+        self.update_idle_inhibit();
+    }
+}
+
+delegate_idle_inhibit!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
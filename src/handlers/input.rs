@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use smithay::{
     backend::{
         input::{
@@ -7,99 +9,209 @@ use smithay::{
         },
         libinput::LibinputInputBackend,
     },
+    desktop::Window,
     input::{
-        keyboard::{xkb, FilterResult},
+        keyboard::{xkb, FilterResult, XkbConfig},
         pointer::{AxisFrame, ButtonEvent, MotionEvent, RelativeMotionEvent},
     },
     reexports::input::Led,
     utils::{Logical, Point, SERIAL_COUNTER},
 };
-use tracing::info;
+use tracing::warn;
 
 use crate::{
     backends::udev::UdevData,
     config::Action,
+    delegate_virtual_keyboard, delegate_virtual_pointer,
+    handlers::pointer_constraints::PointerConfinement,
+    protocols::virtual_input::{VirtualInput, VirtualInputHandler},
     state::{Backend, MagmaState, CONFIG},
     utils::focus::FocusTarget,
-    utils::process,
+    utils::{process, window::WindowExt},
 };
 
 impl MagmaState<UdevData> {
-    pub fn process_input_event_udev(
-        &mut self,
-        event: InputEvent<LibinputInputBackend>,
-    ) -> Option<i32> {
+    pub fn process_input_event_udev(&mut self, event: InputEvent<LibinputInputBackend>) {
         match event {
             InputEvent::Keyboard { event, .. } => {
-                let serial = SERIAL_COUNTER.next_serial();
-                let time = Event::time_msec(&event);
-                if let Some(action) = self.seat.get_keyboard().unwrap().input(
-                    self,
-                    event.key_code(),
-                    event.state(),
-                    serial,
-                    time,
-                    |state, modifiers, handle| {
-                        let mut leds = Led::empty();
-                        if modifiers.caps_lock {
-                            leds.insert(Led::CAPSLOCK);
-                        }
-                        if modifiers.num_lock {
-                            leds.insert(Led::NUMLOCK);
-                        }
-                        event.device().led_update(leds);
-                        #[cfg(feature = "debug")]
-                        if state.debug.egui.wants_keyboard() {
-                            state.debug.egui.handle_keyboard(
-                                &handle,
-                                event.state() == KeyState::Pressed,
-                                *modifiers,
-                            );
-                            return FilterResult::Intercept(None);
-                        }
-                        for (binding, action) in CONFIG.keybindings.iter() {
-                            if event.state() == KeyState::Pressed
-                                && binding.modifiers == *modifiers
-                                && handle.raw_syms().contains(&binding.key)
-                            {
-                                return FilterResult::Intercept(Some(action.clone()));
-                            } else if (xkb::keysyms::KEY_XF86Switch_VT_1
-                                ..=xkb::keysyms::KEY_XF86Switch_VT_12)
-                                .contains(&handle.modified_sym().raw())
-                            {
-                                // VTSwitch
-                                let vt = (handle.modified_sym().raw()
-                                    - xkb::keysyms::KEY_XF86Switch_VT_1
-                                    + 1) as i32;
-                                return FilterResult::Intercept(Some(Action::VTSwitch(vt)));
-                            }
-                        }
-                        FilterResult::Forward
-                    },
-                ) {
-                    match action {
-                        Some(Action::VTSwitch(vt)) => return Some(vt),
-                        Some(action) => self.handle_action(action),
-                        None => {}
-                    }
-                };
-                None
+                self.use_keymap(None, event.time_msec());
+                self.keyboard_input::<LibinputInputBackend>(&event, true);
+                let modifiers = self.seat.get_keyboard().unwrap().modifier_state();
+                let mut leds = Led::empty();
+                if modifiers.caps_lock {
+                    leds.insert(Led::CAPSLOCK);
+                }
+                if modifiers.num_lock {
+                    leds.insert(Led::NUMLOCK);
+                }
+                event.device().led_update(leds);
             }
             InputEvent::DeviceAdded { mut device } => {
                 device.config_tap_set_enabled(true).ok();
                 device.config_tap_set_drag_enabled(true).ok();
-                None
-            }
-            event => {
-                self.process_input_event(event);
-                None
             }
+            event => self.process_input_event(event),
         }
     }
 }
 
 impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Run a key through the keybindings and on to the seat keyboard.
+    // `bindings` is false for keys of clients, which can't trigger compositor actions
+    fn keyboard_input<I: InputBackend>(&mut self, event: &I::KeyboardKeyEvent, bindings: bool) {
+        // releases do not wake outputs, or releasing the key turning them off would
+        let woke = event.state() == KeyState::Pressed && self.notify_activity();
+        let serial = SERIAL_COUNTER.next_serial();
+        let time = Event::time_msec(event);
+        let keyboard = self.seat.get_keyboard().unwrap();
+        // keys an input method grabbed are not taken by the debug overlay
+        #[cfg(feature = "debug")]
+        let grabbed = keyboard.is_grabbed();
+
+        let action = keyboard.input(
+            self,
+            event.key_code(),
+            event.state(),
+            serial,
+            time,
+            |state, modifiers, handle| {
+                // only VT switching works while locked
+                let locked = state.session_lock.is_some();
+                #[cfg(feature = "debug")]
+                if !locked && !grabbed && state.debug.egui.wants_keyboard() {
+                    state.debug.egui.handle_keyboard(
+                        &handle,
+                        event.state() == KeyState::Pressed,
+                        *modifiers,
+                    );
+                    return FilterResult::Intercept(None);
+                }
+                if !bindings || event.state() != KeyState::Pressed {
+                    return FilterResult::Forward;
+                }
+                for (binding, action) in CONFIG.keybindings.iter() {
+                    if binding.modifiers == *modifiers
+                        && handle.raw_syms().contains(&binding.key)
+                        && (!locked || matches!(action, Action::VTSwitch(_)))
+                    {
+                        return FilterResult::Intercept(Some(action.clone()));
+                    }
+                }
+                let sym = handle.modified_sym().raw();
+                if (xkb::keysyms::KEY_XF86Switch_VT_1..=xkb::keysyms::KEY_XF86Switch_VT_12)
+                    .contains(&sym)
+                {
+                    let vt = (sym - xkb::keysyms::KEY_XF86Switch_VT_1 + 1) as i32;
+                    return FilterResult::Intercept(Some(Action::VTSwitch(vt)));
+                }
+                FilterResult::Forward
+            },
+        );
+        match action.flatten() {
+            // the key press turned the outputs on, keep them on
+            Some(Action::ToggleOutputPower(_)) if woke => {}
+            Some(action) => self.handle_action(action),
+            None => {}
+        }
+    }
+
+    /// Switch the seat keyboard to the keymap of a virtual keyboard, or back to the
+    /// configured one with `None`, if it doesn't use it already.
+    fn use_keymap(&mut self, keymap: Option<&Arc<str>>, time: u32) {
+        let unchanged = match (keymap, &self.virtual_keymap) {
+            (Some(keymap), Some(current)) => keymap == current,
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return;
+        }
+        let keyboard = self.seat.get_keyboard().unwrap();
+        // held keys are pressed again in the new xkb state, but locks start out released
+        let modifiers = keyboard.modifier_state();
+        let res = match keymap {
+            Some(keymap) => keyboard.set_keymap_from_string(self, keymap.to_string()),
+            None => keyboard
+                .set_xkb_config(self, (&CONFIG.xkb).into())
+                .or_else(|_| keyboard.set_xkb_config(self, XkbConfig::default())),
+        };
+        match res {
+            Ok(()) => self.virtual_keymap = keymap.cloned(),
+            Err(err) => {
+                warn!(?err, "Failed to switch the keymap");
+                return;
+            }
+        }
+
+        let current = keyboard.modifier_state();
+        let keymap = match keymap {
+            Some(keymap) => xkb::Keymap::new_from_string(
+                &xkb::Context::new(xkb::CONTEXT_NO_FLAGS),
+                keymap.to_string(),
+                xkb::KEYMAP_FORMAT_TEXT_V1,
+                xkb::KEYMAP_COMPILE_NO_FLAGS,
+            ),
+            None => xkb::Keymap::new_from_names(
+                &xkb::Context::new(xkb::CONTEXT_NO_FLAGS),
+                &CONFIG.xkb.rules,
+                &CONFIG.xkb.model,
+                &CONFIG.xkb.layout,
+                &CONFIG.xkb.variant,
+                CONFIG.xkb.options.clone(),
+                xkb::KEYMAP_COMPILE_NO_FLAGS,
+            ),
+        };
+        let Some(keymap) = keymap else {
+            return;
+        };
+        let locks = [
+            (
+                modifiers.caps_lock,
+                current.caps_lock,
+                xkb::keysyms::KEY_Caps_Lock,
+            ),
+            (
+                modifiers.num_lock,
+                current.num_lock,
+                xkb::keysyms::KEY_Num_Lock,
+            ),
+        ];
+        for (locked, now, sym) in locks {
+            if locked == now {
+                continue;
+            }
+            let key = (keymap.min_keycode().raw()..=keymap.max_keycode().raw()).find(|code| {
+                keymap
+                    .key_get_syms_by_level(xkb::Keycode::new(*code), 0, 0)
+                    .iter()
+                    .any(|s| s.raw() == sym)
+            });
+            let Some(key) = key else {
+                continue;
+            };
+            // tapped like on a real keyboard, so focused clients see the lock again
+            for state in [KeyState::Pressed, KeyState::Released] {
+                keyboard.input::<(), _>(
+                    self,
+                    key - 8,
+                    state,
+                    SERIAL_COUNTER.next_serial(),
+                    time,
+                    |_, _, _| FilterResult::Forward,
+                );
+            }
+        }
+    }
+
     pub fn process_input_event<I: InputBackend>(&mut self, event: InputEvent<I>) {
+        if !matches!(
+            event,
+            InputEvent::DeviceAdded { .. }
+                | InputEvent::DeviceRemoved { .. }
+                | InputEvent::Keyboard { .. }
+        ) {
+            self.notify_activity();
+        }
         match event {
             #[cfg(feature = "debug")]
             InputEvent::DeviceAdded { device } => {
@@ -110,80 +222,66 @@ impl<BackendData: Backend> MagmaState<BackendData> {
                 self.debug.egui.handle_device_removed(&device);
             }
             InputEvent::Keyboard { event, .. } => {
-                let serial = SERIAL_COUNTER.next_serial();
-                let time = Event::time_msec(&event);
-
-                if let Some(Some(action)) = self.seat.get_keyboard().unwrap().input(
-                    self,
-                    event.key_code(),
-                    event.state(),
-                    serial,
-                    time,
-                    |state, modifiers, handle| {
-                        #[cfg(feature = "debug")]
-                        if state.debug.egui.wants_keyboard() {
-                            state.debug.egui.handle_keyboard(
-                                &handle,
-                                event.state() == KeyState::Pressed,
-                                *modifiers,
-                            );
-                            return FilterResult::Intercept(None);
-                        }
-                        for (binding, action) in CONFIG.keybindings.iter() {
-                            if event.state() == KeyState::Pressed
-                                && binding.modifiers == *modifiers
-                                && handle.raw_syms().contains(&binding.key)
-                            {
-                                return FilterResult::Intercept(Some(action.clone()));
-                            }
-                        }
-                        FilterResult::Forward
-                    },
-                ) {
-                    self.handle_action(action);
-                };
+                self.use_keymap(None, event.time_msec());
+                self.keyboard_input::<I>(&event, true);
             }
             InputEvent::PointerMotion { event } => {
                 let serial = SERIAL_COUNTER.next_serial();
                 let delta = (event.delta_x(), event.delta_y()).into();
-                self.pointer_location += delta;
+                let pointer = self.seat.get_pointer().unwrap();
+                let relative = RelativeMotionEvent {
+                    delta,
+                    delta_unaccel: event.delta_unaccel(),
+                    utime: event.time(),
+                };
 
-                // clamp to screen limits
-                // this event is never generated by winit
-                self.pointer_location = self.clamp_coords(self.pointer_location);
+                match self.pointer_confinement(&pointer, self.surface_under().as_ref()) {
+                    // the pointer stays where it is, only the client locking it sees it move
+                    Some(PointerConfinement::Locked) => {
+                        let under = self.surface_under();
+                        pointer.relative_motion(self, under, &relative);
+                        return;
+                    }
+                    Some(PointerConfinement::Confined {
+                        surface,
+                        location,
+                        region,
+                    }) => {
+                        self.pointer_location =
+                            self.confine_pointer(delta, &surface, location, region.as_ref());
+                    }
+                    // clamp to screen limits
+                    // this event is never generated by winit
+                    None => {
+                        self.pointer_location = self.clamp_coords(self.pointer_location + delta);
+                    }
+                }
 
                 let under = self.surface_under();
 
                 self.set_input_focus_auto();
 
-                if let Some(ptr) = self.seat.get_pointer() {
-                    ptr.motion(
-                        self,
-                        under.clone(),
-                        &MotionEvent {
-                            location: self.pointer_location,
-                            serial,
-                            time: event.time_msec(),
-                        },
-                    );
-
-                    ptr.relative_motion(
-                        self,
-                        under,
-                        &RelativeMotionEvent {
-                            delta,
-                            delta_unaccel: event.delta_unaccel(),
-                            utime: event.time(),
-                        },
-                    )
-                }
+                pointer.motion(
+                    self,
+                    under.clone(),
+                    &MotionEvent {
+                        location: self.pointer_location,
+                        serial,
+                        time: event.time_msec(),
+                    },
+                );
+                pointer.relative_motion(self, under, &relative);
+                self.activate_pointer_constraint(&pointer);
                 #[cfg(feature = "debug")]
                 self.debug
                     .egui
                     .handle_pointer_motion(self.pointer_location.to_i32_round())
             }
             InputEvent::PointerMotionAbsolute { event, .. } => {
-                let output = self.workspaces.current().outputs().next().unwrap().clone();
+                let output = match self.workspaces.current().outputs().next() {
+                    Some(output) => output.clone(),
+                    None => return,
+                };
 
                 let output_geo = self.workspaces.current().output_geometry(&output).unwrap();
 
@@ -192,8 +290,22 @@ impl<BackendData: Backend> MagmaState<BackendData> {
                 let serial = SERIAL_COUNTER.next_serial();
 
                 let pointer = self.seat.get_pointer().unwrap();
+                // confinement moves by how far the device moved since its last event
+                let delta = pos - self.last_absolute_location.unwrap_or(self.pointer_location);
+                self.last_absolute_location = Some(pos);
 
-                self.pointer_location = self.clamp_coords(pos);
+                match self.pointer_confinement(&pointer, self.surface_under().as_ref()) {
+                    Some(PointerConfinement::Locked) => return,
+                    Some(PointerConfinement::Confined {
+                        surface,
+                        location,
+                        region,
+                    }) => {
+                        self.pointer_location =
+                            self.confine_pointer(delta, &surface, location, region.as_ref());
+                    }
+                    None => self.pointer_location = self.clamp_coords(pos),
+                }
 
                 let under = self.surface_under();
 
@@ -203,11 +315,12 @@ impl<BackendData: Backend> MagmaState<BackendData> {
                     self,
                     under,
                     &MotionEvent {
-                        location: pos,
+                        location: self.pointer_location,
                         serial,
                         time: event.time_msec(),
                     },
                 );
+                self.activate_pointer_constraint(&pointer);
                 #[cfg(feature = "debug")]
                 self.debug
                     .egui
@@ -292,7 +405,7 @@ impl<BackendData: Backend> MagmaState<BackendData> {
         }
     }
 
-    fn clamp_coords(&self, pos: Point<f64, Logical>) -> Point<f64, Logical> {
+    pub fn clamp_coords(&self, pos: Point<f64, Logical>) -> Point<f64, Logical> {
         if self.workspaces.current().outputs().next().is_none() {
             return pos;
         }
@@ -311,18 +424,56 @@ impl<BackendData: Backend> MagmaState<BackendData> {
     }
 
     pub fn set_input_focus(&mut self, target: FocusTarget) {
+        let target = match target {
+            FocusTarget::LockSurface(_) => target,
+            // nothing but the locker may take focus while locked
+            _ if self.session_lock.is_some() => return,
+            // exclusive layers keep the focus until they are closed
+            _ => self
+                .exclusive_layer()
+                .map(FocusTarget::LayerSurface)
+                .unwrap_or(target),
+        };
         let keyboard = self.seat.get_keyboard().unwrap();
         let serial = SERIAL_COUNTER.next_serial();
         keyboard.set_focus(self, Some(target), serial);
     }
 
     pub fn set_input_focus_auto(&mut self) {
+        if let Some(layer) = self
+            .exclusive_layer()
+            .filter(|_| self.session_lock.is_none())
+        {
+            self.set_input_focus(FocusTarget::LayerSurface(layer));
+            return;
+        }
         let under = self.surface_under();
         if let Some(d) = under {
+            match &d.0 {
+                // override redirect windows (menus, tooltips) take focus themselves
+                FocusTarget::Window(w)
+                    if w.x11_surface()
+                        .is_some_and(|x11| x11.is_override_redirect()) =>
+                {
+                    return
+                }
+                // bars and other layers without keyboard interactivity never take focus
+                FocusTarget::LayerSurface(l) if !l.can_receive_keyboard_focus() => return,
+                _ => {}
+            }
             self.set_input_focus(d.0);
         }
     }
 
+    /// Switch to the workspace of `window` and focus it.
+    pub fn focus_window(&mut self, window: &Window) {
+        let Some(id) = self.workspaces.workspace_id_from_window(window) else {
+            return;
+        };
+        self.handle_action(Action::Workspace(id));
+        self.set_input_focus(FocusTarget::Window(window.clone()));
+    }
+
     pub fn handle_action(&mut self, action: Action) {
         match action {
             Action::Quit => self.loop_signal.stop(),
@@ -334,7 +485,7 @@ impl<BackendData: Backend> MagmaState<BackendData> {
                     .current()
                     .window_under(self.pointer_location)
                 {
-                    d.0.toplevel().unwrap().send_close()
+                    d.0.close()
                 }
             }
             Action::Workspace(id) => {
@@ -360,9 +511,41 @@ impl<BackendData: Backend> MagmaState<BackendData> {
             Action::Spawn(command) => {
                 process::spawn(&command);
             }
-            Action::VTSwitch(_) => {
-                info!("VTSwitch is not used in Winit backend.")
+            Action::VTSwitch(vt) => BackendData::switch_vt(self, vt),
+            Action::ToggleOutputPower(name) => {
+                let Some(output) = self
+                    .workspaces
+                    .outputs()
+                    .find(|o| o.name() == name)
+                    .cloned()
+                else {
+                    warn!("No output named {}", name);
+                    return;
+                };
+                let on = !self.backend_data.is_output_powered(&output);
+                BackendData::set_output_power(self, &output, on);
+            }
+            Action::FocusUrgent => {
+                if let Some(window) = self.workspaces.urgent_window() {
+                    self.focus_window(&window);
+                }
             }
         }
     }
 }
+
+impl<BackendData: Backend> VirtualInputHandler for MagmaState<BackendData> {
+    fn virtual_input(&mut self, event: InputEvent<VirtualInput>) {
+        match event {
+            InputEvent::Keyboard { event } => {
+                self.use_keymap(Some(event.keymap()), event.time_msec());
+                self.keyboard_input::<VirtualInput>(&event, false);
+            }
+            event => self.process_input_event(event),
+        }
+    }
+}
+
+delegate_virtual_keyboard!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
+
+delegate_virtual_pointer!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
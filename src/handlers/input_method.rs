@@ -0,0 +1,44 @@
+use smithay::{
+    delegate_input_method_manager, delegate_text_input_manager,
+    desktop::{PopupKind, PopupManager},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Rectangle},
+    wayland::{
+        input_method::{InputMethodHandler, PopupSurface},
+        seat::WaylandFocus,
+    },
+};
+use tracing::warn;
+
+use crate::state::{Backend, MagmaState};
+
+impl<BackendData: Backend> InputMethodHandler for MagmaState<BackendData> {
+    // the popup is placed below the text cursor rectangle of the focused text input and drawn
+    // with the popups of its parent
+    fn new_popup(&mut self, surface: PopupSurface) {
+        if let Err(err) = self.popup_manager.track_popup(PopupKind::from(surface)) {
+            warn!("Failed to track input method popup: {}", err);
+        }
+    }
+
+    // the new location is picked up on the next render
+    fn popup_repositioned(&mut self, _surface: PopupSurface) {}
+
+    fn dismiss_popup(&mut self, surface: PopupSurface) {
+        if let Some(parent) = surface.get_parent().map(|parent| parent.surface.clone()) {
+            let _ = PopupManager::dismiss_popup(&parent, &PopupKind::from(surface));
+        }
+    }
+
+    fn parent_geometry(&self, parent: &WlSurface) -> Rectangle<i32, Logical> {
+        self.workspaces
+            .all_windows()
+            .find(|w| w.wl_surface().as_ref() == Some(parent))
+            .map(|w| w.geometry())
+            .unwrap_or_default()
+    }
+}
+
+delegate_input_method_manager!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
+
+delegate_text_input_manager!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
@@ -1,19 +1,20 @@
 use smithay::{
     backend::renderer::utils::on_commit_buffer_handler,
-    delegate_compositor, delegate_data_device, delegate_layer_shell, delegate_output,
-    delegate_primary_selection, delegate_seat, delegate_shm,
-    desktop::{layer_map_for_output, LayerSurface},
-    input::{SeatHandler, SeatState},
+    delegate_compositor, delegate_data_control, delegate_data_device, delegate_layer_shell,
+    delegate_output, delegate_primary_selection, delegate_seat, delegate_shm,
+    desktop::{layer_map_for_output, LayerSurface, WindowSurfaceType},
+    input::{Seat, SeatHandler, SeatState},
     output::Output,
     reexports::wayland_server::{
-        protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+        protocol::{wl_data_source::WlDataSource, wl_output::WlOutput, wl_surface::WlSurface},
         Client, Resource,
     },
+    utils::SERIAL_COUNTER,
     wayland::{
         buffer::BufferHandler,
         compositor::{
-            get_parent, is_sync_subsurface, CompositorClientState, CompositorHandler,
-            CompositorState,
+            get_parent, is_sync_subsurface, with_states, CompositorClientState, CompositorHandler,
+            CompositorState, SurfaceAttributes,
         },
         output::OutputHandler,
         seat::WaylandFocus,
@@ -25,22 +26,39 @@ use smithay::{
             primary_selection::{
                 set_primary_focus, PrimarySelectionHandler, PrimarySelectionState,
             },
-            SelectionHandler,
+            wlr_data_control::{DataControlHandler, DataControlState},
+            SelectionHandler, SelectionSource, SelectionTarget,
         },
         shell::wlr_layer::{
             Layer, LayerSurface as WlrLayerSurface, WlrLayerShellHandler, WlrLayerShellState,
         },
         shm::{ShmHandler, ShmState},
     },
+    xwayland::XWaylandClientData,
 };
 
+use std::os::fd::OwnedFd;
+use tracing::warn;
+
 use crate::{
-    state::{Backend, ClientState, MagmaState},
-    utils::{focus::FocusTarget, tiling::bsp_update_layout},
+    state::{Backend, ClientState, DndIcon, MagmaState, CONFIG},
+    utils::{focus::FocusTarget, window::WindowExt},
 };
 
+use self::clipboard::CompositorSelection;
+
+pub mod clipboard;
+pub mod foreign_toplevel;
+pub mod fractional_scale;
+pub mod idle;
 pub mod input;
+pub mod input_method;
+pub mod pointer_constraints;
+pub mod presentation;
+pub mod session_lock;
+pub mod xdg_activation;
 pub mod xdg_shell;
+pub mod xwayland;
 
 impl<BackendData: Backend> CompositorHandler for MagmaState<BackendData> {
     fn compositor_state(&mut self) -> &mut CompositorState {
@@ -48,10 +66,23 @@ impl<BackendData: Backend> CompositorHandler for MagmaState<BackendData> {
     }
 
     fn client_compositor_state<'a>(&self, client: &'a Client) -> &'a CompositorClientState {
+        if let Some(state) = client.get_data::<XWaylandClientData>() {
+            return &state.compositor_state;
+        }
         &client.get_data::<ClientState>().unwrap().compositor_state
     }
 
     fn commit(&mut self, surface: &WlSurface) {
+        // the buffer handler consumes the offset of the new buffer
+        if let Some(icon) = self.dnd_icon.as_mut().filter(|i| &i.surface == surface) {
+            icon.offset += with_states(surface, |states| {
+                states
+                    .cached_state
+                    .current::<SurfaceAttributes>()
+                    .buffer_delta
+                    .unwrap_or_default()
+            });
+        }
         on_commit_buffer_handler::<Self>(surface);
         if !is_sync_subsurface(surface) {
             let mut root = surface.clone();
@@ -61,13 +92,36 @@ impl<BackendData: Backend> CompositorHandler for MagmaState<BackendData> {
             if let Some(window) = self
                 .workspaces
                 .all_windows()
-                .find(|w| w.toplevel().unwrap().wl_surface() == &root)
+                .find(|w| w.wl_surface().as_ref() == Some(&root))
             {
                 window.on_commit();
             }
         };
         self.popup_manager.commit(surface);
-        xdg_shell::handle_commit(&self.workspaces, surface, &self.popup_manager);
+        xdg_shell::handle_commit(&mut self.workspaces, surface, &self.popup_manager);
+        self.layer_focus_changed(surface);
+    }
+}
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    // apply the keyboard interactivity a layer surface committed
+    fn layer_focus_changed(&mut self, surface: &WlSurface) {
+        let Some(layer) = self.workspaces.outputs().find_map(|o| {
+            layer_map_for_output(o)
+                .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                .cloned()
+        }) else {
+            return;
+        };
+        let keyboard = self.seat.get_keyboard().unwrap();
+        let focused = keyboard.current_focus() == Some(FocusTarget::LayerSurface(layer.clone()));
+
+        if !focused && self.exclusive_layer().as_ref() == Some(&layer) {
+            self.set_input_focus(FocusTarget::LayerSurface(layer));
+        } else if focused && !layer.can_receive_keyboard_focus() {
+            keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+            self.set_input_focus_auto();
+        }
     }
 }
 
@@ -101,8 +155,9 @@ impl<BackendData: Backend> SeatHandler for MagmaState<BackendData> {
     fn cursor_image(
         &mut self,
         _seat: &smithay::input::Seat<Self>,
-        _image: smithay::input::pointer::CursorImageStatus,
+        image: smithay::input::pointer::CursorImageStatus,
     ) {
+        self.cursor_status = image;
     }
     fn focus_changed(&mut self, seat: &smithay::input::Seat<Self>, focused: Option<&FocusTarget>) {
         let dh = &self.dh;
@@ -116,22 +171,23 @@ impl<BackendData: Backend> SeatHandler for MagmaState<BackendData> {
         if let Some(focus_target) = focused {
             match focus_target {
                 FocusTarget::Window(w) => {
+                    self.workspaces.set_urgent(w, false);
                     for window in self.workspaces.all_windows() {
                         if window.eq(w) {
                             window.set_activated(true);
                         } else {
                             window.set_activated(false);
                         }
-                        window.toplevel().unwrap().send_configure();
+                        window.send_configure();
                     }
                 }
                 FocusTarget::LayerSurface(_) => {
                     for window in self.workspaces.all_windows() {
                         window.set_activated(false);
-                        window.toplevel().unwrap().send_configure();
+                        window.send_configure();
                     }
                 }
-                FocusTarget::Popup(_) => {}
+                FocusTarget::Popup(_) | FocusTarget::LockSurface(_) => {}
             };
         }
     }
@@ -144,7 +200,61 @@ delegate_seat!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
 //
 
 impl<BackendData: Backend> SelectionHandler for MagmaState<BackendData> {
-    type SelectionUserData = ();
+    type SelectionUserData = CompositorSelection;
+
+    fn new_selection(
+        &mut self,
+        ty: SelectionTarget,
+        source: Option<SelectionSource>,
+        _seat: Seat<Self>,
+    ) {
+        let mime_types = source.map(|source| source.mime_types());
+        if CONFIG.clipboard_persistence && matches!(ty, SelectionTarget::Clipboard) {
+            match &mime_types {
+                Some(mime_types) => {
+                    // only the focused client can set the selection through the data device
+                    let owner = self
+                        .seat
+                        .get_keyboard()
+                        .and_then(|keyboard| keyboard.current_focus())
+                        .and_then(|focus| focus.wl_surface())
+                        .and_then(|surface| surface.client());
+                    self.persist_clipboard(mime_types.clone(), owner)
+                }
+                // the client went away, or cleared the clipboard itself
+                None => self.clipboard_cleared(),
+            }
+        }
+        if let Some(xwm) = self.xwm.as_mut() {
+            if let Err(err) = xwm.new_selection(ty, mime_types) {
+                warn!(?err, ?ty, "Failed to set XWayland selection");
+            }
+        }
+    }
+
+    fn send_selection(
+        &mut self,
+        ty: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+        _seat: Seat<Self>,
+        user_data: &CompositorSelection,
+    ) {
+        match user_data {
+            CompositorSelection::Persisted(contents) => {
+                self.send_persisted(contents, &mime_type, fd)
+            }
+            CompositorSelection::Xwayland => {
+                if let Some(xwm) = self.xwm.as_mut() {
+                    if let Err(err) =
+                        xwm.send_selection(ty, mime_type, fd, self.loop_handle.clone())
+                    {
+                        warn!(?err, "Failed to send selection to XWayland");
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl<BackendData: Backend> DataDeviceHandler for MagmaState<BackendData> {
@@ -153,7 +263,26 @@ impl<BackendData: Backend> DataDeviceHandler for MagmaState<BackendData> {
     }
 }
 
-impl<BackendData: Backend> ClientDndGrabHandler for MagmaState<BackendData> {}
+impl<BackendData: Backend> ClientDndGrabHandler for MagmaState<BackendData> {
+    fn started(
+        &mut self,
+        _source: Option<WlDataSource>,
+        icon: Option<WlSurface>,
+        _seat: Seat<Self>,
+    ) {
+        self.dnd_icon = icon.map(|surface| DndIcon {
+            surface,
+            offset: (0, 0).into(),
+        });
+    }
+
+    // the grab already told the source whether a client accepted the drop, or cancelled it
+    fn dropped(&mut self, _seat: Seat<Self>) {
+        self.dnd_icon = None;
+    }
+}
+
+// drags are only ever started by clients
 impl<BackendData: Backend> ServerDndGrabHandler for MagmaState<BackendData> {}
 
 delegate_data_device!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
@@ -165,6 +294,14 @@ impl<BackendData: Backend> PrimarySelectionHandler for MagmaState<BackendData> {
 }
 
 delegate_primary_selection!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
+
+impl<BackendData: Backend> DataControlHandler for MagmaState<BackendData> {
+    fn data_control_state(&self) -> &DataControlState {
+        &self.data_control_state
+    }
+}
+
+delegate_data_control!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
 //
 // Wl Output & Xdg Output
 //
@@ -184,35 +321,45 @@ impl<BackendData: Backend> WlrLayerShellHandler for MagmaState<BackendData> {
         _layer: Layer,
         namespace: String,
     ) {
-        let output = output
-            .as_ref()
-            .and_then(Output::from_resource)
-            .unwrap_or_else(|| self.workspaces.current().outputs().next().unwrap().clone());
+        // without an output asked for, use the one under the pointer
+        let output = output.as_ref().and_then(Output::from_resource).or_else(|| {
+            let workspace = self.workspaces.current();
+            workspace
+                .outputs()
+                .find(|o| {
+                    workspace
+                        .output_geometry(o)
+                        .is_some_and(|geo| geo.to_f64().contains(self.pointer_location))
+                })
+                .or_else(|| workspace.outputs().next())
+                .cloned()
+        });
+        let Some(output) = output else {
+            warn!("No output for layer surface {}", namespace);
+            surface.send_close();
+            return;
+        };
+        // focus and exclusive zone are known after the initial commit, see `commit`
         let mut map = layer_map_for_output(&output);
-        let layer_surface = LayerSurface::new(surface, namespace);
-        map.map_layer(&layer_surface).unwrap();
-        self.set_input_focus(FocusTarget::LayerSurface(layer_surface));
-        drop(map);
-        for workspace in self.workspaces.iter() {
-            bsp_update_layout(workspace);
+        if let Err(err) = map.map_layer(&LayerSurface::new(surface, namespace)) {
+            warn!("Failed to map layer surface: {}", err);
         }
     }
 
     fn layer_destroyed(&mut self, surface: WlrLayerSurface) {
-        if let Some((mut map, layer)) = self.workspaces.outputs().find_map(|o| {
-            let map = layer_map_for_output(o);
+        let output = self.workspaces.outputs().find_map(|o| {
+            let mut map = layer_map_for_output(o);
             let layer = map
                 .layers()
                 .find(|&layer| layer.layer_surface() == &surface)
-                .cloned();
-            layer.map(|layer| (map, layer))
-        }) {
+                .cloned()?;
             map.unmap_layer(&layer);
+            Some(o.clone())
+        });
+        if let Some(output) = output {
+            self.workspaces.update_output_layout(&output);
         }
         self.set_input_focus_auto();
-        for workspace in self.workspaces.iter() {
-            bsp_update_layout(workspace);
-        }
     }
 }
 
@@ -0,0 +1,139 @@
+use smithay::{
+    delegate_pointer_constraints, delegate_relative_pointer,
+    input::pointer::PointerHandle,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point},
+    wayland::{
+        compositor::RegionAttributes,
+        pointer_constraints::{
+            with_pointer_constraint, PointerConstraint, PointerConstraintsHandler,
+        },
+        seat::WaylandFocus,
+    },
+};
+
+use crate::{
+    state::{Backend, MagmaState},
+    utils::focus::FocusTarget,
+};
+
+/// An active constraint on the surface under the pointer.
+pub enum PointerConfinement {
+    /// The pointer must not move, only relative motion is sent
+    Locked,
+    /// The pointer must stay within the surface and region
+    Confined {
+        surface: WlSurface,
+        location: Point<i32, Logical>,
+        region: Option<RegionAttributes>,
+    },
+}
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// The constraint of the surface under the pointer, if it is active.
+    pub fn pointer_confinement(
+        &self,
+        pointer: &PointerHandle<Self>,
+        under: Option<&(FocusTarget, Point<i32, Logical>)>,
+    ) -> Option<PointerConfinement> {
+        let (target, location) = under?;
+        let surface = target.wl_surface()?;
+        with_pointer_constraint(&surface, pointer, |constraint| {
+            let constraint = constraint.filter(|c| c.is_active())?;
+            Some(match &*constraint {
+                PointerConstraint::Locked(_) => PointerConfinement::Locked,
+                PointerConstraint::Confined(confined) => PointerConfinement::Confined {
+                    surface: surface.clone(),
+                    location: *location,
+                    region: confined.region().cloned(),
+                },
+            })
+        })
+    }
+
+    /// Move the pointer by `delta` as far as it can go without leaving its confinement.
+    ///
+    /// The pointer slides along the edges of the region instead of stopping on them.
+    pub fn confine_pointer(
+        &self,
+        delta: Point<f64, Logical>,
+        surface: &WlSurface,
+        location: Point<i32, Logical>,
+        region: Option<&RegionAttributes>,
+    ) -> Point<f64, Logical> {
+        let current = self.pointer_location;
+        let target = self.clamp_coords(current + delta);
+        [
+            target,
+            (target.x, current.y).into(),
+            (current.x, target.y).into(),
+        ]
+        .into_iter()
+        .find(|pos| {
+            let inside = region.map_or(true, |region| {
+                region.contains((*pos - location.to_f64()).to_i32_round())
+            });
+            inside
+                && self
+                    .surface_under_at(*pos)
+                    .and_then(|(target, _)| target.wl_surface())
+                    .as_ref()
+                    == Some(surface)
+        })
+        .unwrap_or(current)
+    }
+
+    /// Activate the constraint of the surface under the pointer once the pointer entered its region.
+    pub fn activate_pointer_constraint(&self, pointer: &PointerHandle<Self>) {
+        let Some((target, location)) = self.surface_under() else {
+            return;
+        };
+        let Some(surface) = target.wl_surface() else {
+            return;
+        };
+        let point = (self.pointer_location - location.to_f64()).to_i32_round();
+        with_pointer_constraint(&surface, pointer, |constraint| match constraint {
+            Some(constraint) if !constraint.is_active() => {
+                if constraint
+                    .region()
+                    .map_or(true, |region| region.contains(point))
+                {
+                    constraint.activate();
+                }
+            }
+            _ => {}
+        });
+    }
+}
+
+impl<BackendData: Backend> PointerConstraintsHandler for MagmaState<BackendData> {
+    fn new_constraint(&mut self, _surface: &WlSurface, pointer: &PointerHandle<Self>) {
+        self.activate_pointer_constraint(pointer);
+    }
+
+    fn cursor_position_hint(
+        &mut self,
+        surface: &WlSurface,
+        pointer: &PointerHandle<Self>,
+        location: Point<f64, Logical>,
+    ) {
+        let locked = with_pointer_constraint(surface, pointer, |constraint| {
+            constraint.is_some_and(|c| c.is_active())
+        });
+        if !locked {
+            return;
+        }
+        // the pointer can't leave the locked surface, and the client hides it meanwhile, so
+        // moving it right away leaves it at the hint once unlocked
+        if let Some((_, origin)) = self
+            .surface_under()
+            .filter(|(target, _)| target.wl_surface().as_ref() == Some(surface))
+        {
+            self.pointer_location = origin.to_f64() + location;
+        }
+    }
+}
+
+delegate_pointer_constraints!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
+
+delegate_relative_pointer!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
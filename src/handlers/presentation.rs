@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use smithay::{
+    backend::renderer::element::{default_primary_scanout_output_compare, RenderElementStates},
+    delegate_presentation,
+    desktop::{
+        layer_map_for_output,
+        utils::{
+            send_frames_surface_tree, surface_presentation_feedback_flags_from_states,
+            surface_primary_scanout_output, take_presentation_feedback_surface_tree,
+            update_surface_primary_scanout_output, with_surfaces_surface_tree,
+            OutputPresentationFeedback,
+        },
+    },
+    input::pointer::CursorImageStatus,
+    output::Output,
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    wayland::{
+        compositor::{with_states, SurfaceData},
+        seat::WaylandFocus,
+    },
+};
+
+use crate::{
+    delegate_tearing_control,
+    protocols::tearing_control::wants_tearing,
+    state::{Backend, MagmaState},
+};
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Remember which surfaces were just rendered to `output`, see [`Self::send_frames`].
+    pub fn update_primary_scanout_output(&self, output: &Output, states: &RenderElementStates) {
+        let update = |surface: &WlSurface, data: &SurfaceData| {
+            update_surface_primary_scanout_output(
+                surface,
+                output,
+                data,
+                states,
+                default_primary_scanout_output_compare,
+            );
+        };
+        for window in self.workspaces.current().windows() {
+            window.with_surfaces(update);
+        }
+        for window in &self.override_redirect {
+            window.with_surfaces(update);
+        }
+        for layer in layer_map_for_output(output).layers() {
+            layer.with_surfaces(update);
+        }
+        if let Some(lock) = self.session_lock.as_ref().and_then(|l| l.surface(output)) {
+            with_surfaces_surface_tree(lock.wl_surface(), update);
+        }
+        if let CursorImageStatus::Surface(cursor) = &self.cursor_status {
+            with_surfaces_surface_tree(cursor, update);
+        }
+        if let Some(icon) = self.dnd_icon.as_ref().filter(|i| i.surface.is_alive()) {
+            with_surfaces_surface_tree(&icon.surface, update);
+        }
+    }
+
+    /// Collect the presentation feedback of the surfaces in a frame queued on `output`.
+    pub fn take_presentation_feedback(
+        &self,
+        output: &Output,
+        states: &RenderElementStates,
+    ) -> OutputPresentationFeedback {
+        let mut feedback = OutputPresentationFeedback::new(output);
+        let flags = |surface: &WlSurface, _: &SurfaceData| {
+            surface_presentation_feedback_flags_from_states(surface, states)
+        };
+        for window in self.workspaces.current().windows() {
+            window.take_presentation_feedback(&mut feedback, surface_primary_scanout_output, flags);
+        }
+        for window in &self.override_redirect {
+            window.take_presentation_feedback(&mut feedback, surface_primary_scanout_output, flags);
+        }
+        for layer in layer_map_for_output(output).layers() {
+            layer.take_presentation_feedback(&mut feedback, surface_primary_scanout_output, flags);
+        }
+        if let Some(lock) = self.session_lock.as_ref().and_then(|l| l.surface(output)) {
+            take_presentation_feedback_surface_tree(
+                lock.wl_surface(),
+                &mut feedback,
+                surface_primary_scanout_output,
+                flags,
+            );
+        }
+        feedback
+    }
+
+    /// Whether the window covering `output` allows its frames to tear for lower latency.
+    pub fn allows_tearing(&self, output: &Output) -> bool {
+        self.session_lock.is_none()
+            && self
+                .workspaces
+                .current()
+                .fullscreen_window(output)
+                .and_then(|window| window.wl_surface())
+                .is_some_and(|surface| with_states(&surface, wants_tearing))
+    }
+
+    /// Send frame callbacks to the surfaces last shown on `output`.
+    pub fn send_frames(&self, output: &Output) {
+        let time = self.start_time.elapsed();
+        // surfaces shown elsewhere get theirs from the output they are on
+        let throttle = None;
+        for window in self.workspaces.current().windows() {
+            window.send_frame(output, time, throttle, surface_primary_scanout_output);
+        }
+        for window in &self.override_redirect {
+            window.send_frame(output, time, throttle, surface_primary_scanout_output);
+        }
+        for layer in layer_map_for_output(output).layers() {
+            layer.send_frame(output, time, throttle, surface_primary_scanout_output);
+        }
+        if let Some(lock) = self.session_lock.as_ref().and_then(|l| l.surface(output)) {
+            send_frames_surface_tree(
+                lock.wl_surface(),
+                output,
+                time,
+                throttle,
+                surface_primary_scanout_output,
+            );
+        }
+        if let CursorImageStatus::Surface(cursor) = &self.cursor_status {
+            send_frames_surface_tree(
+                cursor,
+                output,
+                time,
+                throttle,
+                surface_primary_scanout_output,
+            );
+        }
+        if let Some(icon) = self.dnd_icon.as_ref().filter(|i| i.surface.is_alive()) {
+            send_frames_surface_tree(
+                &icon.surface,
+                output,
+                time,
+                throttle,
+                surface_primary_scanout_output,
+            );
+        }
+    }
+}
+
+/// The refresh interval of `output` in nanoseconds for presentation feedback, 0 if unknown.
+pub fn refresh_interval(output: &Output) -> u32 {
+    output
+        .current_mode()
+        .filter(|mode| mode.refresh > 0)
+        .map_or(0, |mode| {
+            Duration::from_secs_f64(1_000.0 / mode.refresh as f64).as_nanos() as u32
+        })
+}
+
+delegate_presentation!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
+
+delegate_tearing_control!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
@@ -0,0 +1,109 @@
+use smithay::{
+    delegate_session_lock,
+    output::Output,
+    reexports::wayland_server::protocol::wl_output::WlOutput,
+    utils::{Size, SERIAL_COUNTER},
+    wayland::session_lock::{
+        LockSurface, SessionLockHandler, SessionLockManagerState, SessionLocker,
+    },
+};
+use tracing::info;
+
+use crate::{
+    state::{Backend, MagmaState},
+    utils::{focus::FocusTarget, render::output_size},
+};
+
+/// Lock surfaces of the outputs while the session is locked.
+///
+/// Stays around if the locker dies without unlocking, outputs without a
+/// lock surface are then only cleared with a solid colour.
+#[derive(Default)]
+pub struct SessionLock {
+    surfaces: Vec<(Output, LockSurface)>,
+    // confirmed once every output showed a locked frame, see `confirm_session_lock`
+    locker: Option<SessionLocker>,
+    presented: Vec<Output>,
+}
+
+impl SessionLock {
+    pub fn surface(&self, output: &Output) -> Option<&LockSurface> {
+        self.surfaces
+            .iter()
+            .find(|(o, _)| o == output)
+            .map(|(_, surface)| surface)
+    }
+
+    /// A frame rendered while locked is now shown on `output`.
+    pub fn frame_presented(&mut self, output: &Output) {
+        if self.locker.is_some() && !self.presented.contains(output) {
+            self.presented.push(output.clone());
+        }
+    }
+}
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Tell the locker the session is locked, once nothing but the lock is on screen anymore.
+    pub fn confirm_session_lock(&mut self) {
+        let Some(lock) = self.session_lock.as_ref() else {
+            return;
+        };
+        // outputs turned off don't show anything
+        let shown = self
+            .workspaces
+            .outputs()
+            .filter(|output| self.backend_data.is_output_powered(output))
+            .all(|output| lock.presented.contains(output));
+        if !shown {
+            return;
+        }
+        if let Some(locker) = self.session_lock.as_mut().and_then(|l| l.locker.take()) {
+            info!("Session locked");
+            locker.lock();
+        }
+    }
+}
+
+impl<BackendData: Backend> SessionLockHandler for MagmaState<BackendData> {
+    fn lock_state(&mut self) -> &mut SessionLockManagerState {
+        &mut self.session_lock_state
+    }
+
+    fn lock(&mut self, confirmation: SessionLocker) {
+        info!("Locking the session");
+        // nothing but lock surfaces is rendered from now on
+        self.session_lock = Some(SessionLock {
+            locker: Some(confirmation),
+            ..Default::default()
+        });
+        let keyboard = self.seat.get_keyboard().unwrap();
+        keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+    }
+
+    fn unlock(&mut self) {
+        info!("Unlocking the session");
+        self.session_lock = None;
+        self.set_input_focus_auto();
+    }
+
+    fn new_surface(&mut self, surface: LockSurface, output: WlOutput) {
+        let Some(output) = Output::from_resource(&output) else {
+            return;
+        };
+        let Some(lock) = self.session_lock.as_mut() else {
+            return;
+        };
+        if output.current_mode().is_some() {
+            let size = output_size(&output);
+            surface.with_pending_state(|state| {
+                state.size = Some(Size::from((size.w as u32, size.h as u32)));
+            });
+            surface.send_configure();
+        }
+        lock.surfaces.retain(|(o, _)| o != &output);
+        lock.surfaces.push((output, surface.clone()));
+        self.set_input_focus(FocusTarget::LockSurface(surface));
+    }
+}
+
+delegate_session_lock!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
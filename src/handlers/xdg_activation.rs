@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use smithay::{
+    delegate_xdg_activation,
+    input::Seat,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::{
+        seat::WaylandFocus,
+        xdg_activation::{
+            XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData,
+        },
+    },
+};
+use tracing::debug;
+
+use crate::state::{Backend, MagmaState};
+
+/// How long a token may be used to take the focus.
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    // only tokens issued for recent input to the focused client may move the focus,
+    // the focus changing since then makes the token stale
+    fn activation_is_valid(&self, data: &XdgActivationTokenData) -> bool {
+        let Some((serial, seat)) = &data.serial else {
+            return false;
+        };
+        let keyboard = self.seat.get_keyboard().unwrap();
+        Seat::<Self>::from_resource(seat).as_ref() == Some(&self.seat)
+            && keyboard
+                .last_enter()
+                .is_some_and(|last_enter| serial.is_no_older_than(&last_enter))
+            && data.timestamp.elapsed() < ACTIVATION_TIMEOUT
+    }
+}
+
+impl<BackendData: Backend> XdgActivationHandler for MagmaState<BackendData> {
+    fn activation_state(&mut self) -> &mut XdgActivationState {
+        &mut self.xdg_activation_state
+    }
+
+    fn request_activation(
+        &mut self,
+        token: XdgActivationToken,
+        token_data: XdgActivationTokenData,
+        surface: WlSurface,
+    ) {
+        // tokens can be used once
+        self.xdg_activation_state.remove_token(&token);
+        let Some(window) = self
+            .workspaces
+            .all_windows()
+            .find(|w| w.wl_surface().as_ref() == Some(&surface))
+            .map(|w| w.clone())
+        else {
+            return;
+        };
+        if self.activation_is_valid(&token_data) {
+            self.focus_window(&window);
+        } else {
+            debug!("Stale activation request, marking the window as urgent");
+            self.workspaces.set_urgent(&window, true);
+        }
+    }
+}
+
+delegate_xdg_activation!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
@@ -1,8 +1,11 @@
 use smithay::{
     delegate_xdg_decoration, delegate_xdg_shell,
     desktop::{
-        PopupKind, PopupManager, WindowSurfaceType, {layer_map_for_output, Window},
+        find_popup_root_surface, get_popup_toplevel_coords, PopupKeyboardGrab, PopupKind,
+        PopupManager, PopupPointerGrab, PopupUngrabStrategy, WindowSurfaceType,
+        {layer_map_for_output, Window},
     },
+    input::{pointer::Focus, Seat},
     reexports::{
         wayland_protocols::xdg::{
             decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode,
@@ -13,6 +16,7 @@ use smithay::{
     utils::Serial,
     wayland::{
         compositor::with_states,
+        seat::WaylandFocus,
         shell::{
             wlr_layer::LayerSurfaceData,
             xdg::{
@@ -27,9 +31,12 @@ use std::{cell::RefCell, rc::Rc, sync::Mutex};
 use tracing::warn;
 
 use crate::{
-    state::{Backend, MagmaState},
+    config::Decoration,
+    state::{Backend, MagmaState, CONFIG},
     utils::{
         focus::FocusTarget,
+        tiling::bsp_update_layout,
+        window::WindowExt,
         workspace::{MagmaWindow, Workspaces},
     },
 };
@@ -46,6 +53,7 @@ impl<BackendData: Backend> XdgShellHandler for MagmaState<BackendData> {
             .add_window(Rc::new(RefCell::new(MagmaWindow {
                 window: window.clone(),
                 rec: window.geometry(),
+                urgent: false,
             })));
         self.set_input_focus(FocusTarget::Window(window));
     }
@@ -53,7 +61,7 @@ impl<BackendData: Backend> XdgShellHandler for MagmaState<BackendData> {
         let window = self
             .workspaces
             .all_windows()
-            .find(|w| w.toplevel().unwrap() == &surface)
+            .find(|w| w.toplevel() == Some(&surface))
             .unwrap()
             .clone();
 
@@ -67,32 +75,138 @@ impl<BackendData: Backend> XdgShellHandler for MagmaState<BackendData> {
         surface.with_pending_state(|state| {
             state.geometry = positioner.get_geometry();
         });
+        self.unconstrain_popup(&surface);
         if let Err(err) = self.popup_manager.track_popup(PopupKind::from(surface)) {
             warn!("Failed to track popup: {}", err);
         }
     }
 
-    fn grab(&mut self, _surface: PopupSurface, _seat: WlSeat, _serial: Serial) {
-        // TODO popup grabs
+    fn grab(&mut self, surface: PopupSurface, seat: WlSeat, serial: Serial) {
+        let seat: Seat<Self> = Seat::from_resource(&seat).unwrap();
+        let kind = PopupKind::Xdg(surface);
+        let Some(root) = find_popup_root_surface(&kind)
+            .ok()
+            .and_then(|root| self.focus_target_for_surface(&root))
+        else {
+            return;
+        };
+
+        let mut grab = match self.popup_manager.grab_popup(root, kind, &seat, serial) {
+            Ok(grab) => grab,
+            Err(err) => {
+                warn!("Failed to grab popup: {:?}", err);
+                return;
+            }
+        };
+
+        // only grab if the client was allowed to, i.e. the serial belongs to the current grab
+        if let Some(keyboard) = seat.get_keyboard() {
+            if keyboard.is_grabbed()
+                && !(keyboard.has_grab(serial)
+                    || keyboard.has_grab(grab.previous_serial().unwrap_or(serial)))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            keyboard.set_focus(self, grab.current_grab(), serial);
+            keyboard.set_grab(PopupKeyboardGrab::new(&grab), serial);
+        }
+        if let Some(pointer) = seat.get_pointer() {
+            if pointer.is_grabbed()
+                && !(pointer.has_grab(serial)
+                    || pointer.has_grab(grab.previous_serial().unwrap_or_else(|| grab.serial())))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
+        }
     }
 
     fn reposition_request(
         &mut self,
-        _surface: PopupSurface,
-        _positioner: PositionerState,
-        _token: u32,
+        surface: PopupSurface,
+        positioner: PositionerState,
+        token: u32,
     ) {
-        todo!()
+        surface.with_pending_state(|state| {
+            state.geometry = positioner.get_geometry();
+            state.positioner = positioner;
+        });
+        self.unconstrain_popup(&surface);
+        surface.send_repositioned(token);
+    }
+}
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    // the window or layer surface a toplevel surface belongs to
+    fn focus_target_for_surface(&self, surface: &WlSurface) -> Option<FocusTarget> {
+        if let Some(window) = self
+            .workspaces
+            .all_windows()
+            .find(|w| w.wl_surface().as_ref() == Some(surface))
+        {
+            return Some(FocusTarget::Window(window.clone()));
+        }
+        self.workspaces.outputs().find_map(|o| {
+            layer_map_for_output(o)
+                .layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                .cloned()
+                .map(FocusTarget::LayerSurface)
+        })
+    }
+
+    /// Move or resize a popup so it stays within the output of its parent.
+    fn unconstrain_popup(&self, popup: &PopupSurface) {
+        let Ok(root) = find_popup_root_surface(&PopupKind::Xdg(popup.clone())) else {
+            return;
+        };
+
+        // location of the root's window geometry in global coordinates
+        let (root_location, output) = if let Some(window) = self
+            .workspaces
+            .current()
+            .magmawindows()
+            .find(|w| w.window.wl_surface().as_ref() == Some(&root))
+        {
+            let output = self.workspaces.current().outputs().next().cloned();
+            (window.client_rec().loc, output)
+        } else if let Some((location, output)) = self.workspaces.current().outputs().find_map(|o| {
+            let map = layer_map_for_output(o);
+            let layer = map.layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)?;
+            Some((map.layer_geometry(layer)?.loc, o.clone()))
+        }) {
+            (location, Some(output))
+        } else {
+            return;
+        };
+        let Some(output_geo) = output.and_then(|o| self.workspaces.current().output_geometry(&o))
+        else {
+            return;
+        };
+
+        // the target rectangle is relative to the parent of the popup
+        let mut target = output_geo;
+        target.loc -= root_location;
+        target.loc -= get_popup_toplevel_coords(&PopupKind::Xdg(popup.clone()));
+        popup.with_pending_state(|state| {
+            state.geometry = state.positioner.get_unconstrained_geometry(target);
+        });
     }
 }
 
 delegate_xdg_shell!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
 
 // Should be called on `WlSurface::commit`
-pub fn handle_commit(workspaces: &Workspaces, surface: &WlSurface, popup_manager: &PopupManager) {
+pub fn handle_commit(
+    workspaces: &mut Workspaces,
+    surface: &WlSurface,
+    popup_manager: &PopupManager,
+) {
     if let Some(window) = workspaces
         .all_windows()
-        .find(|w| w.toplevel().unwrap().wl_surface() == surface)
+        .find(|w| w.toplevel().is_some_and(|t| t.wl_surface() == surface))
+        .map(|w| w.clone())
     {
         let initial_configure_sent = with_states(surface, |states| {
             states
@@ -104,22 +218,39 @@ pub fn handle_commit(workspaces: &Workspaces, surface: &WlSurface, popup_manager
                 .initial_configure_sent
         });
         if !initial_configure_sent {
+            // the app id is known by now, so window rules can match
+            let rule_mode = rule_decoration_mode(&window);
             let toplevel = window.toplevel();
             toplevel.unwrap().with_pending_state(|state| {
                 state.states.set(ToplevelState::TiledLeft);
                 state.states.set(ToplevelState::TiledRight);
                 state.states.set(ToplevelState::TiledTop);
                 state.states.set(ToplevelState::TiledBottom);
+                // only for clients using xdg-decoration, the others draw their own
+                if state.decoration_mode.is_some() {
+                    if let Some(mode) = rule_mode {
+                        state.decoration_mode = Some(mode);
+                    }
+                }
             });
+            // the decoration mode decides if space is left for a title bar
+            if let Some(workspace) = workspaces.workspace_from_window(&window) {
+                bsp_update_layout(workspace);
+            }
             toplevel.unwrap().send_configure();
         }
     }
 
-    if let Some(output) = workspaces.current().outputs().find(|o| {
-        let map = layer_map_for_output(o);
-        map.layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
-            .is_some()
-    }) {
+    if let Some(output) = workspaces
+        .current()
+        .outputs()
+        .find(|o| {
+            let map = layer_map_for_output(o);
+            map.layer_for_surface(surface, WindowSurfaceType::TOPLEVEL)
+                .is_some()
+        })
+        .cloned()
+    {
         let initial_configure_sent = with_states(surface, |states| {
             states
                 .data_map
@@ -129,7 +260,8 @@ pub fn handle_commit(workspaces: &Workspaces, surface: &WlSurface, popup_manager
                 .unwrap()
                 .initial_configure_sent
         });
-        let mut map = layer_map_for_output(output);
+        let mut map = layer_map_for_output(&output);
+        let zone = map.non_exclusive_zone();
 
         // arrange the layers before sending the initial configure
         // to respect any size the client may have sent
@@ -142,6 +274,11 @@ pub fn handle_commit(workspaces: &Workspaces, surface: &WlSurface, popup_manager
 
             layer.layer_surface().send_configure();
         }
+        let zone_changed = map.non_exclusive_zone() != zone;
+        drop(map);
+        if zone_changed {
+            workspaces.update_output_layout(&output);
+        }
     };
 
     if let Some(popup) = popup_manager.find_popup(surface) {
@@ -169,24 +306,66 @@ pub fn handle_commit(workspaces: &Workspaces, surface: &WlSurface, popup_manager
     };
 }
 
-// Disable decorations
 impl<BackendData: Backend> XdgDecorationHandler for MagmaState<BackendData> {
     fn new_decoration(&mut self, toplevel: ToplevelSurface) {
+        self.set_decoration_mode(&toplevel, None);
+    }
+
+    fn request_mode(&mut self, toplevel: ToplevelSurface, mode: Mode) {
+        self.set_decoration_mode(&toplevel, Some(mode));
+    }
+
+    fn unset_mode(&mut self, toplevel: ToplevelSurface) {
+        self.set_decoration_mode(&toplevel, None);
+    }
+}
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Use the decoration mode the client asked for unless a window rule overrides it,
+    /// preferring server side decorations.
+    fn set_decoration_mode(&mut self, toplevel: &ToplevelSurface, requested: Option<Mode>) {
+        let window = self
+            .workspaces
+            .all_windows()
+            .find(|w| w.toplevel() == Some(toplevel))
+            .map(|w| w.clone());
+        let mode = window
+            .as_ref()
+            .and_then(rule_decoration_mode)
+            .or(requested)
+            .unwrap_or(Mode::ServerSide);
         toplevel.with_pending_state(|state| {
-            // Advertise server side decoration
-            state.decoration_mode = Some(Mode::ServerSide);
+            state.decoration_mode = Some(mode);
         });
-        toplevel.send_configure();
-    }
 
-    fn request_mode(
-        &mut self,
-        _toplevel: ToplevelSurface,
-        _mode: smithay::reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode,
-    ) {
+        let initial_configure_sent = with_states(toplevel.wl_surface(), |states| {
+            states
+                .data_map
+                .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .initial_configure_sent
+        });
+        // otherwise the mode is sent with the initial configure
+        if initial_configure_sent {
+            // the title bar changes the size left to the client
+            match window.and_then(|w| self.workspaces.workspace_from_window(&w)) {
+                Some(workspace) => bsp_update_layout(workspace),
+                None => toplevel.send_configure(),
+            }
+        }
     }
+}
 
-    fn unset_mode(&mut self, _toplevel: ToplevelSurface) {}
+// decoration mode forced by a window rule
+fn rule_decoration_mode(window: &Window) -> Option<Mode> {
+    CONFIG
+        .decoration(&window.app_id(), &window.title())
+        .map(|decoration| match decoration {
+            Decoration::Client => Mode::ClientSide,
+            Decoration::Server => Mode::ServerSide,
+        })
 }
 
 delegate_xdg_decoration!(@<BackendData: Backend + 'static> MagmaState<BackendData>);
@@ -0,0 +1,354 @@
+use std::{cell::RefCell, os::fd::OwnedFd, process::Stdio, rc::Rc};
+
+use smithay::{
+    desktop::{space::SpaceElement, Window},
+    input::pointer::CursorIcon,
+    reexports::x11rb::protocol::xproto::Window as X11Window,
+    utils::{Logical, Point, Rectangle, Size},
+    wayland::selection::{
+        data_device::{
+            clear_data_device_selection, current_data_device_selection_userdata,
+            request_data_device_client_selection, set_data_device_selection,
+        },
+        primary_selection::{
+            clear_primary_selection, current_primary_selection_userdata,
+            request_primary_client_selection, set_primary_selection,
+        },
+        SelectionTarget,
+    },
+    xwayland::{
+        xwm::{Reorder, ResizeEdge as X11ResizeEdge, WmWindowProperty, XwmId},
+        X11Surface, X11Wm, XWayland, XWaylandEvent, XwmHandler,
+    },
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    handlers::clipboard::CompositorSelection,
+    state::{Backend, CalloopData, MagmaState, CONFIG},
+    utils::{focus::FocusTarget, process, workspace::MagmaWindow},
+};
+
+impl<BackendData: Backend> MagmaState<BackendData> {
+    /// Spawn XWayland once the event loop runs and start the autostart commands
+    /// after `DISPLAY` is known, so X11 clients started from there work.
+    pub fn start_xwayland(&mut self) {
+        if !CONFIG.xwayland {
+            process::spawn_autostart();
+            return;
+        }
+        self.loop_handle
+            .insert_idle(|data| data.state.spawn_xwayland(true));
+    }
+
+    fn spawn_xwayland(&mut self, autostart: bool) {
+        let (xwayland, client) = match XWayland::spawn(
+            &self.dh,
+            None,
+            std::iter::empty::<(String, String)>(),
+            true,
+            Stdio::null(),
+            Stdio::null(),
+            |_| (),
+        ) {
+            Ok(xwayland) => xwayland,
+            Err(err) => {
+                error!("Failed to spawn XWayland: {}", err);
+                if autostart {
+                    process::spawn_autostart();
+                }
+                return;
+            }
+        };
+
+        let res = self
+            .loop_handle
+            .insert_source(xwayland, move |event, _, data| match event {
+                XWaylandEvent::Ready {
+                    x11_socket,
+                    display_number,
+                } => {
+                    match X11Wm::start_wm(
+                        data.state.loop_handle.clone(),
+                        x11_socket,
+                        client.clone(),
+                    ) {
+                        Ok(mut wm) => {
+                            let (pixels, size, hotspot) =
+                                data.state.cursor_theme.image(CursorIcon::Default);
+                            if let Err(err) = wm.set_cursor(
+                                pixels,
+                                Size::from((size.0 as u16, size.1 as u16)),
+                                Point::from((hotspot.0 as u16, hotspot.1 as u16)),
+                            ) {
+                                warn!("Failed to set XWayland cursor: {}", err);
+                            }
+                            data.state.xwm = Some(wm);
+                            data.state.xdisplay = Some(display_number);
+                            std::env::set_var("DISPLAY", format!(":{}", display_number));
+                            info!("XWayland ready on DISPLAY=:{}", display_number);
+                        }
+                        Err(err) => error!("Failed to start the X11 window manager: {}", err),
+                    }
+                    if autostart {
+                        process::spawn_autostart();
+                    }
+                }
+                XWaylandEvent::Error => {
+                    warn!("XWayland crashed on startup");
+                    if autostart {
+                        process::spawn_autostart();
+                    }
+                }
+            });
+        if let Err(err) = res {
+            error!("Failed to insert XWayland into the event loop: {}", err);
+            if autostart {
+                process::spawn_autostart();
+            }
+        }
+    }
+}
+
+impl<BackendData: Backend> XwmHandler for CalloopData<BackendData> {
+    fn xwm_state(&mut self, _xwm: XwmId) -> &mut X11Wm {
+        self.state.xwm.as_mut().unwrap()
+    }
+
+    fn new_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+    fn new_override_redirect_window(&mut self, _xwm: XwmId, _window: X11Surface) {}
+
+    fn map_window_request(&mut self, _xwm: XwmId, surface: X11Surface) {
+        if let Err(err) = surface.set_mapped(true) {
+            warn!("Failed to map X11 window: {}", err);
+            return;
+        }
+        let window = Window::new_x11_window(surface);
+        self.state
+            .workspaces
+            .current_mut()
+            .add_window(Rc::new(RefCell::new(MagmaWindow {
+                window: window.clone(),
+                rec: window.geometry(),
+                urgent: false,
+            })));
+        self.state.set_input_focus(FocusTarget::Window(window));
+    }
+
+    fn mapped_override_redirect_window(&mut self, _xwm: XwmId, surface: X11Surface) {
+        // menus and tooltips position themselves and are not tiled
+        self.state
+            .override_redirect
+            .push(Window::new_x11_window(surface));
+    }
+
+    fn unmapped_window(&mut self, _xwm: XwmId, surface: X11Surface) {
+        self.state
+            .override_redirect
+            .retain(|w| w.x11_surface() != Some(&surface));
+
+        let window = self
+            .state
+            .workspaces
+            .all_windows()
+            .find(|w| w.x11_surface() == Some(&surface))
+            .map(|w| w.clone());
+        if let Some(window) = window {
+            self.state
+                .workspaces
+                .workspace_from_window(&window)
+                .unwrap()
+                .remove_window(&window);
+            self.state.set_input_focus_auto();
+        }
+        if !surface.is_override_redirect() {
+            surface.set_mapped(false).ok();
+        }
+    }
+
+    fn destroyed_window(&mut self, _xwm: XwmId, _surface: X11Surface) {}
+
+    fn disconnected(&mut self, xwm: XwmId) {
+        warn!("XWayland exited, restarting it");
+        let state = &mut self.state;
+        state.xwm = None;
+        state.xdisplay = None;
+        std::env::remove_var("DISPLAY");
+
+        // the windows of the old server are gone with it
+        state
+            .override_redirect
+            .retain(|w| w.x11_surface().and_then(X11Surface::xwm_id) != Some(xwm));
+        let windows = state
+            .workspaces
+            .all_windows()
+            .filter(|w| w.x11_surface().and_then(X11Surface::xwm_id) == Some(xwm))
+            .map(|w| w.clone())
+            .collect::<Vec<_>>();
+        for window in &windows {
+            if let Some(workspace) = state.workspaces.workspace_from_window(window) {
+                workspace.remove_window(window);
+            }
+        }
+        state.set_input_focus_auto();
+
+        state
+            .loop_handle
+            .insert_idle(|data| data.state.spawn_xwayland(false));
+    }
+
+    fn property_notify(&mut self, _xwm: XwmId, surface: X11Surface, property: WmWindowProperty) {
+        if !matches!(property, WmWindowProperty::Hints) {
+            return;
+        }
+        let window = self
+            .state
+            .workspaces
+            .all_windows()
+            .find(|w| w.x11_surface() == Some(&surface))
+            .map(|w| w.clone());
+        if let Some(window) = window {
+            // the focused window already has our attention
+            let focused = self.state.seat.get_keyboard().unwrap().current_focus()
+                == Some(FocusTarget::Window(window.clone()));
+            let urgent = surface.hints().is_some_and(|hints| hints.urgent);
+            self.state
+                .workspaces
+                .set_urgent(&window, urgent && !focused);
+        }
+    }
+
+    fn configure_request(
+        &mut self,
+        _xwm: XwmId,
+        surface: X11Surface,
+        x: Option<i32>,
+        y: Option<i32>,
+        w: Option<u32>,
+        h: Option<u32>,
+        _reorder: Option<Reorder>,
+    ) {
+        let tiled = self
+            .state
+            .workspaces
+            .all_windows()
+            .any(|window| window.x11_surface() == Some(&surface));
+        if tiled {
+            // the layout decides the geometry, resend it
+            surface.configure(surface.geometry()).ok();
+            return;
+        }
+
+        let mut geometry = surface.geometry();
+        if let Some(x) = x {
+            geometry.loc.x = x;
+        }
+        if let Some(y) = y {
+            geometry.loc.y = y;
+        }
+        if let Some(w) = w {
+            geometry.size.w = w as i32;
+        }
+        if let Some(h) = h {
+            geometry.size.h = h as i32;
+        }
+        surface.configure(geometry).ok();
+    }
+
+    fn configure_notify(
+        &mut self,
+        _xwm: XwmId,
+        _surface: X11Surface,
+        _geometry: Rectangle<i32, Logical>,
+        _above: Option<X11Window>,
+    ) {
+    }
+
+    fn resize_request(
+        &mut self,
+        _xwm: XwmId,
+        _surface: X11Surface,
+        _button: u32,
+        _resize_edge: X11ResizeEdge,
+    ) {
+    }
+
+    fn move_request(&mut self, _xwm: XwmId, _surface: X11Surface, _button: u32) {}
+
+    fn allow_selection_access(&mut self, xwm: XwmId, _selection: SelectionTarget) -> bool {
+        // only the focused X11 client may read the selection
+        let keyboard = self.state.seat.get_keyboard().unwrap();
+        matches!(
+            keyboard.current_focus(),
+            Some(FocusTarget::Window(w))
+                if w.x11_surface().and_then(X11Surface::xwm_id) == Some(xwm)
+        )
+    }
+
+    fn send_selection(
+        &mut self,
+        _xwm: XwmId,
+        selection: SelectionTarget,
+        mime_type: String,
+        fd: OwnedFd,
+    ) {
+        let res = match selection {
+            SelectionTarget::Clipboard => {
+                if let Some(CompositorSelection::Persisted(contents)) =
+                    current_data_device_selection_userdata(&self.state.seat)
+                {
+                    self.state.send_persisted(&contents, &mime_type, fd);
+                    return;
+                }
+                request_data_device_client_selection(&self.state.seat, mime_type, fd)
+            }
+            SelectionTarget::Primary => {
+                request_primary_client_selection(&self.state.seat, mime_type, fd)
+            }
+        };
+        if let Err(err) = res {
+            warn!(
+                "Failed to send {:?} selection to XWayland: {:?}",
+                selection, err
+            );
+        }
+    }
+
+    fn new_selection(&mut self, _xwm: XwmId, selection: SelectionTarget, mime_types: Vec<String>) {
+        match selection {
+            SelectionTarget::Clipboard => set_data_device_selection(
+                &self.state.dh,
+                &self.state.seat,
+                mime_types,
+                CompositorSelection::Xwayland,
+            ),
+            SelectionTarget::Primary => set_primary_selection(
+                &self.state.dh,
+                &self.state.seat,
+                mime_types,
+                CompositorSelection::Xwayland,
+            ),
+        }
+    }
+
+    fn cleared_selection(&mut self, _xwm: XwmId, selection: SelectionTarget) {
+        match selection {
+            SelectionTarget::Clipboard => {
+                if matches!(
+                    current_data_device_selection_userdata(&self.state.seat),
+                    Some(CompositorSelection::Xwayland)
+                ) {
+                    clear_data_device_selection(&self.state.dh, &self.state.seat)
+                }
+            }
+            SelectionTarget::Primary => {
+                if matches!(
+                    current_primary_selection_userdata(&self.state.seat),
+                    Some(CompositorSelection::Xwayland)
+                ) {
+                    clear_primary_selection(&self.state.dh, &self.state.seat)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! wlr-foreign-toplevel-management protocol.
+
+use _foreign_toplevel::{
+    zwlr_foreign_toplevel_handle_v1::{self, State, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+use smithay::desktop::Window;
+use smithay::output::Output;
+use smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server as _foreign_toplevel;
+use smithay::reexports::wayland_server::{
+    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+};
+
+const MANAGER_VERSION: u32 = 3;
+
+/// What taskbars are told about a toplevel.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToplevelInfo {
+    pub title: String,
+    pub app_id: String,
+    pub activated: bool,
+    /// Demanding attention, taskbars are told the toplevel is activated so they
+    /// highlight it, as the protocol has no state of its own for this.
+    pub urgent: bool,
+    /// The output the toplevel is shown on, `None` while its workspace is hidden.
+    pub output: Option<Output>,
+}
+
+struct Toplevel {
+    window: Window,
+    info: ToplevelInfo,
+    handles: Vec<ZwlrForeignToplevelHandleV1>,
+}
+
+pub struct ForeignToplevelManagerState {
+    dh: DisplayHandle,
+    managers: Vec<ZwlrForeignToplevelManagerV1>,
+    toplevels: Vec<Toplevel>,
+}
+
+impl ForeignToplevelManagerState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ()>,
+        D: Dispatch<ZwlrForeignToplevelManagerV1, ()>,
+        D: Dispatch<ZwlrForeignToplevelHandleV1, ()>,
+        D: ForeignToplevelHandler,
+        D: 'static,
+    {
+        display.create_global::<D, ZwlrForeignToplevelManagerV1, _>(MANAGER_VERSION, ());
+
+        Self {
+            dh: display.clone(),
+            managers: Vec::new(),
+            toplevels: Vec::new(),
+        }
+    }
+
+    /// Advertise `window` or send what changed about it since the last update.
+    pub fn update<D>(&mut self, window: &Window, info: ToplevelInfo)
+    where
+        D: Dispatch<ZwlrForeignToplevelHandleV1, ()>,
+        D: 'static,
+    {
+        if let Some(toplevel) = self.toplevels.iter_mut().find(|t| &t.window == window) {
+            if toplevel.info == info {
+                return;
+            }
+            for handle in &toplevel.handles {
+                send_changes(handle, Some(&toplevel.info), &info);
+            }
+            toplevel.info = info;
+            return;
+        }
+
+        let mut toplevel = Toplevel {
+            window: window.clone(),
+            info,
+            handles: Vec::new(),
+        };
+        for manager in &self.managers {
+            if let Some(handle) = send_toplevel::<D>(&self.dh, manager, &toplevel.info) {
+                toplevel.handles.push(handle);
+            }
+        }
+        self.toplevels.push(toplevel);
+    }
+
+    /// Withdraw every toplevel whose window `f` returns false for.
+    pub fn retain(&mut self, mut f: impl FnMut(&Window) -> bool) {
+        self.toplevels.retain(|toplevel| {
+            if f(&toplevel.window) {
+                return true;
+            }
+            for handle in &toplevel.handles {
+                handle.closed();
+            }
+            false
+        });
+    }
+
+    fn window_for_handle(&self, handle: &ZwlrForeignToplevelHandleV1) -> Option<Window> {
+        self.toplevels
+            .iter()
+            .find(|t| t.handles.contains(handle))
+            .map(|t| t.window.clone())
+    }
+}
+
+fn send_toplevel<D>(
+    dh: &DisplayHandle,
+    manager: &ZwlrForeignToplevelManagerV1,
+    info: &ToplevelInfo,
+) -> Option<ZwlrForeignToplevelHandleV1>
+where
+    D: Dispatch<ZwlrForeignToplevelHandleV1, ()>,
+    D: 'static,
+{
+    let client = manager.client()?;
+    let handle = client
+        .create_resource::<ZwlrForeignToplevelHandleV1, _, D>(dh, manager.version(), ())
+        .ok()?;
+    manager.toplevel(&handle);
+    send_changes(&handle, None, info);
+    Some(handle)
+}
+
+// Sends the fields of `new` that differ from `old`, or all of them without `old`,
+// followed by `done`.
+fn send_changes(
+    handle: &ZwlrForeignToplevelHandleV1,
+    old: Option<&ToplevelInfo>,
+    new: &ToplevelInfo,
+) {
+    if old.map_or(true, |old| old.title != new.title) {
+        handle.title(new.title.clone());
+    }
+    if old.map_or(true, |old| old.app_id != new.app_id) {
+        handle.app_id(new.app_id.clone());
+    }
+    let old_output = old.and_then(|old| old.output.as_ref());
+    if old.is_none() || old_output != new.output.as_ref() {
+        if let Some(client) = handle.client() {
+            if let Some(output) = old_output {
+                for wl_output in output.client_outputs(&client) {
+                    handle.output_leave(&wl_output);
+                }
+            }
+            if let Some(output) = &new.output {
+                for wl_output in output.client_outputs(&client) {
+                    handle.output_enter(&wl_output);
+                }
+            }
+        }
+    }
+    let states: Vec<u8> = (new.activated || new.urgent)
+        .then_some(State::Activated as u32)
+        .into_iter()
+        .flat_map(u32::to_ne_bytes)
+        .collect();
+    handle.state(states);
+    handle.done();
+}
+
+impl<D> GlobalDispatch<ZwlrForeignToplevelManagerV1, (), D> for ForeignToplevelManagerState
+where
+    D: GlobalDispatch<ZwlrForeignToplevelManagerV1, ()>,
+    D: Dispatch<ZwlrForeignToplevelManagerV1, ()>,
+    D: Dispatch<ZwlrForeignToplevelHandleV1, ()>,
+    D: ForeignToplevelHandler,
+    D: 'static,
+{
+    fn bind(
+        state: &mut D,
+        display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ZwlrForeignToplevelManagerV1>,
+        _manager_state: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(manager, ());
+        let foreign_toplevel_state = state.foreign_toplevel_manager_state();
+        for toplevel in &mut foreign_toplevel_state.toplevels {
+            if let Some(handle) = send_toplevel::<D>(display, &manager, &toplevel.info) {
+                toplevel.handles.push(handle);
+            }
+        }
+        foreign_toplevel_state.managers.push(manager);
+    }
+}
+
+impl<D> Dispatch<ZwlrForeignToplevelManagerV1, (), D> for ForeignToplevelManagerState
+where
+    D: Dispatch<ZwlrForeignToplevelManagerV1, ()>,
+    D: Dispatch<ZwlrForeignToplevelHandleV1, ()>,
+    D: ForeignToplevelHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        manager: &ZwlrForeignToplevelManagerV1,
+        request: zwlr_foreign_toplevel_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_foreign_toplevel_manager_v1::Request::Stop => {
+                state
+                    .foreign_toplevel_manager_state()
+                    .managers
+                    .retain(|m| m != manager);
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        manager: &ZwlrForeignToplevelManagerV1,
+        _data: &(),
+    ) {
+        state
+            .foreign_toplevel_manager_state()
+            .managers
+            .retain(|m| m != manager);
+    }
+}
+
+impl<D> Dispatch<ZwlrForeignToplevelHandleV1, (), D> for ForeignToplevelManagerState
+where
+    D: Dispatch<ZwlrForeignToplevelHandleV1, ()>,
+    D: ForeignToplevelHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        handle: &ZwlrForeignToplevelHandleV1,
+        request: zwlr_foreign_toplevel_handle_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        // the toplevel may be closed already
+        let Some(window) = state
+            .foreign_toplevel_manager_state()
+            .window_for_handle(handle)
+        else {
+            return;
+        };
+        match request {
+            zwlr_foreign_toplevel_handle_v1::Request::Activate { .. } => state.activate(window),
+            zwlr_foreign_toplevel_handle_v1::Request::Close => state.close(window),
+            zwlr_foreign_toplevel_handle_v1::Request::SetMaximized => {
+                state.set_maximized(window, true)
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMaximized => {
+                state.set_maximized(window, false)
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetMinimized => {
+                state.set_minimized(window, true)
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMinimized => {
+                state.set_minimized(window, false)
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetFullscreen { .. } => {
+                state.set_fullscreen(window, true)
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetFullscreen => {
+                state.set_fullscreen(window, false)
+            }
+            // minimize animations are not a thing here
+            zwlr_foreign_toplevel_handle_v1::Request::SetRectangle { .. } => {}
+            zwlr_foreign_toplevel_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        handle: &ZwlrForeignToplevelHandleV1,
+        _data: &(),
+    ) {
+        for toplevel in &mut state.foreign_toplevel_manager_state().toplevels {
+            toplevel.handles.retain(|h| h != handle);
+        }
+    }
+}
+
+/// Handler trait for wlr-foreign-toplevel-management.
+pub trait ForeignToplevelHandler {
+    fn foreign_toplevel_manager_state(&mut self) -> &mut ForeignToplevelManagerState;
+
+    /// Show and focus `window`.
+    fn activate(&mut self, window: Window);
+
+    /// Ask `window` to close.
+    fn close(&mut self, window: Window);
+
+    fn set_maximized(&mut self, window: Window, maximized: bool);
+
+    fn set_minimized(&mut self, window: Window, minimized: bool);
+
+    fn set_fullscreen(&mut self, window: Window, fullscreen: bool);
+}
+
+#[allow(missing_docs)]
+#[macro_export]
+macro_rules! delegate_foreign_toplevel {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: ()
+        ] => $crate::protocols::foreign_toplevel::ForeignToplevelManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1: ()
+        ] => $crate::protocols::foreign_toplevel::ForeignToplevelManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::foreign_toplevel::v1::server::zwlr_foreign_toplevel_handle_v1::ZwlrForeignToplevelHandleV1: ()
+        ] => $crate::protocols::foreign_toplevel::ForeignToplevelManagerState);
+    };
+}
@@ -0,0 +1,243 @@
+//! ext-foreign-toplevel-list protocol.
+
+use _foreign_toplevel_list::{
+    ext_foreign_toplevel_handle_v1::{self, ExtForeignToplevelHandleV1},
+    ext_foreign_toplevel_list_v1::{self, ExtForeignToplevelListV1},
+};
+use smithay::desktop::Window;
+use smithay::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server as _foreign_toplevel_list;
+use smithay::reexports::wayland_server::{
+    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+};
+
+const LIST_VERSION: u32 = 1;
+
+struct Toplevel {
+    window: Window,
+    // stays the same for the whole lifetime of the window
+    identifier: String,
+    title: String,
+    app_id: String,
+    handles: Vec<ExtForeignToplevelHandleV1>,
+}
+
+pub struct ForeignToplevelListState {
+    dh: DisplayHandle,
+    next_identifier: u64,
+    lists: Vec<ExtForeignToplevelListV1>,
+    toplevels: Vec<Toplevel>,
+}
+
+impl ForeignToplevelListState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ExtForeignToplevelListV1, ()>,
+        D: Dispatch<ExtForeignToplevelListV1, ()>,
+        D: Dispatch<ExtForeignToplevelHandleV1, ()>,
+        D: ForeignToplevelListHandler,
+        D: 'static,
+    {
+        display.create_global::<D, ExtForeignToplevelListV1, _>(LIST_VERSION, ());
+
+        Self {
+            dh: display.clone(),
+            next_identifier: 0,
+            lists: Vec::new(),
+            toplevels: Vec::new(),
+        }
+    }
+
+    /// Advertise `window` or send its new title and app id.
+    pub fn update<D>(&mut self, window: &Window, title: String, app_id: String)
+    where
+        D: Dispatch<ExtForeignToplevelHandleV1, ()>,
+        D: 'static,
+    {
+        if let Some(toplevel) = self.toplevels.iter_mut().find(|t| &t.window == window) {
+            if toplevel.title == title && toplevel.app_id == app_id {
+                return;
+            }
+            for handle in &toplevel.handles {
+                if toplevel.title != title {
+                    handle.title(title.clone());
+                }
+                if toplevel.app_id != app_id {
+                    handle.app_id(app_id.clone());
+                }
+                handle.done();
+            }
+            toplevel.title = title;
+            toplevel.app_id = app_id;
+            return;
+        }
+
+        self.next_identifier += 1;
+        let mut toplevel = Toplevel {
+            window: window.clone(),
+            identifier: format!("{:016x}", self.next_identifier),
+            title,
+            app_id,
+            handles: Vec::new(),
+        };
+        for list in &self.lists {
+            if let Some(handle) = send_toplevel::<D>(&self.dh, list, &toplevel) {
+                toplevel.handles.push(handle);
+            }
+        }
+        self.toplevels.push(toplevel);
+    }
+
+    /// Withdraw every toplevel whose window `f` returns false for.
+    pub fn retain(&mut self, mut f: impl FnMut(&Window) -> bool) {
+        self.toplevels.retain(|toplevel| {
+            if f(&toplevel.window) {
+                return true;
+            }
+            for handle in &toplevel.handles {
+                handle.closed();
+            }
+            false
+        });
+    }
+}
+
+fn send_toplevel<D>(
+    dh: &DisplayHandle,
+    list: &ExtForeignToplevelListV1,
+    toplevel: &Toplevel,
+) -> Option<ExtForeignToplevelHandleV1>
+where
+    D: Dispatch<ExtForeignToplevelHandleV1, ()>,
+    D: 'static,
+{
+    let client = list.client()?;
+    let handle = client
+        .create_resource::<ExtForeignToplevelHandleV1, _, D>(dh, list.version(), ())
+        .ok()?;
+    list.toplevel(&handle);
+    handle.identifier(toplevel.identifier.clone());
+    handle.title(toplevel.title.clone());
+    handle.app_id(toplevel.app_id.clone());
+    handle.done();
+    Some(handle)
+}
+
+impl<D> GlobalDispatch<ExtForeignToplevelListV1, (), D> for ForeignToplevelListState
+where
+    D: GlobalDispatch<ExtForeignToplevelListV1, ()>,
+    D: Dispatch<ExtForeignToplevelListV1, ()>,
+    D: Dispatch<ExtForeignToplevelHandleV1, ()>,
+    D: ForeignToplevelListHandler,
+    D: 'static,
+{
+    fn bind(
+        state: &mut D,
+        display: &DisplayHandle,
+        _client: &Client,
+        list: New<ExtForeignToplevelListV1>,
+        _list_state: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let list = data_init.init(list, ());
+        let list_state = state.foreign_toplevel_list_state();
+        for toplevel in &mut list_state.toplevels {
+            if let Some(handle) = send_toplevel::<D>(display, &list, toplevel) {
+                toplevel.handles.push(handle);
+            }
+        }
+        list_state.lists.push(list);
+    }
+}
+
+impl<D> Dispatch<ExtForeignToplevelListV1, (), D> for ForeignToplevelListState
+where
+    D: Dispatch<ExtForeignToplevelListV1, ()>,
+    D: Dispatch<ExtForeignToplevelHandleV1, ()>,
+    D: ForeignToplevelListHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        list: &ExtForeignToplevelListV1,
+        request: ext_foreign_toplevel_list_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_foreign_toplevel_list_v1::Request::Stop => {
+                state
+                    .foreign_toplevel_list_state()
+                    .lists
+                    .retain(|l| l != list);
+                list.finished();
+            }
+            ext_foreign_toplevel_list_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, list: &ExtForeignToplevelListV1, _data: &()) {
+        state
+            .foreign_toplevel_list_state()
+            .lists
+            .retain(|l| l != list);
+    }
+}
+
+impl<D> Dispatch<ExtForeignToplevelHandleV1, (), D> for ForeignToplevelListState
+where
+    D: Dispatch<ExtForeignToplevelHandleV1, ()>,
+    D: ForeignToplevelListHandler,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _handle: &ExtForeignToplevelHandleV1,
+        request: ext_foreign_toplevel_handle_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            ext_foreign_toplevel_handle_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        handle: &ExtForeignToplevelHandleV1,
+        _data: &(),
+    ) {
+        for toplevel in &mut state.foreign_toplevel_list_state().toplevels {
+            toplevel.handles.retain(|h| h != handle);
+        }
+    }
+}
+
+/// Handler trait for ext-foreign-toplevel-list.
+pub trait ForeignToplevelListHandler {
+    fn foreign_toplevel_list_state(&mut self) -> &mut ForeignToplevelListState;
+}
+
+#[allow(missing_docs)]
+#[macro_export]
+macro_rules! delegate_foreign_toplevel_list {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: ()
+        ] => $crate::protocols::foreign_toplevel_list::ForeignToplevelListState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_list_v1::ExtForeignToplevelListV1: ()
+        ] => $crate::protocols::foreign_toplevel_list::ForeignToplevelListState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::ext::foreign_toplevel_list::v1::server::ext_foreign_toplevel_handle_v1::ExtForeignToplevelHandleV1: ()
+        ] => $crate::protocols::foreign_toplevel_list::ForeignToplevelListState);
+    };
+}
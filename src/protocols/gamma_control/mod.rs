@@ -0,0 +1,231 @@
+//! wlr-gamma-control protocol.
+
+use std::{fs::File, io::Read};
+
+use _gamma::{
+    zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+    zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+use smithay::output::Output;
+use smithay::reexports::rustix::fs::{fcntl_setfl, OFlags};
+use smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server as _gamma;
+use smithay::reexports::wayland_server::{
+    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+};
+use tracing::warn;
+
+const MANAGER_VERSION: u32 = 1;
+
+pub struct GammaControlManagerState {
+    // at most one active control per output
+    controls: Vec<(Output, ZwlrGammaControlV1)>,
+}
+
+impl GammaControlManagerState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwlrGammaControlManagerV1, ()>,
+        D: Dispatch<ZwlrGammaControlManagerV1, ()>,
+        D: Dispatch<ZwlrGammaControlV1, Option<Output>>,
+        D: GammaControlHandler,
+        D: 'static,
+    {
+        display.create_global::<D, ZwlrGammaControlManagerV1, _>(MANAGER_VERSION, ());
+
+        Self {
+            controls: Vec::new(),
+        }
+    }
+
+    /// Invalidate the gamma control of an output that was disconnected.
+    pub fn remove_output(&mut self, output: &Output) {
+        self.controls.retain(|(o, control)| {
+            if o == output {
+                control.failed();
+            }
+            o != output
+        });
+    }
+
+    fn is_active(&self, control: &ZwlrGammaControlV1) -> bool {
+        self.controls.iter().any(|(_, c)| c == control)
+    }
+
+    // returns whether the control was active
+    fn remove(&mut self, control: &ZwlrGammaControlV1) -> bool {
+        let active = self.is_active(control);
+        self.controls.retain(|(_, c)| c != control);
+        active
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlManagerState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, ()>,
+    D: Dispatch<ZwlrGammaControlManagerV1, ()>,
+    D: Dispatch<ZwlrGammaControlV1, Option<Output>>,
+    D: GammaControlHandler,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ZwlrGammaControlManagerV1>,
+        _manager_state: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlManagerState
+where
+    D: Dispatch<ZwlrGammaControlManagerV1, ()>,
+    D: Dispatch<ZwlrGammaControlV1, Option<Output>>,
+    D: GammaControlHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _manager: &ZwlrGammaControlManagerV1,
+        request: zwlr_gamma_control_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                let output = Output::from_resource(&output);
+                let control = data_init.init(id, output.clone());
+                let Some(output) = output else {
+                    // the output is already gone
+                    control.failed();
+                    return;
+                };
+                let taken = state
+                    .gamma_control_manager_state()
+                    .controls
+                    .iter()
+                    .any(|(o, _)| o == &output);
+                match state.gamma_size(&output) {
+                    Some(size) if !taken => {
+                        control.gamma_size(size);
+                        state
+                            .gamma_control_manager_state()
+                            .controls
+                            .push((output, control));
+                    }
+                    _ => control.failed(),
+                }
+            }
+            zwlr_gamma_control_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlV1, Option<Output>, D> for GammaControlManagerState
+where
+    D: Dispatch<ZwlrGammaControlV1, Option<Output>>,
+    D: GammaControlHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        control: &ZwlrGammaControlV1,
+        request: zwlr_gamma_control_v1::Request,
+        output: &Option<Output>,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                let Some(output) = output else {
+                    return;
+                };
+                if !state.gamma_control_manager_state().is_active(control) {
+                    return;
+                }
+                let Some(size) = state.gamma_size(output) else {
+                    control.failed();
+                    state.gamma_control_manager_state().remove(control);
+                    return;
+                };
+
+                // red, green and blue ramps of native endian u16s, already written by the
+                // client, it is not waited for
+                let mut bytes = vec![0u8; size as usize * 3 * 2];
+                let read = fcntl_setfl(&fd, OFlags::NONBLOCK)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| File::from(fd).read_exact(&mut bytes));
+                if let Err(err) = read {
+                    warn!("Failed to read gamma ramps: {}", err);
+                    control.post_error(
+                        zwlr_gamma_control_v1::Error::InvalidGamma,
+                        "gamma ramps have the wrong size",
+                    );
+                    return;
+                }
+                let ramps = bytes
+                    .chunks_exact(2)
+                    .map(|b| u16::from_ne_bytes([b[0], b[1]]))
+                    .collect();
+
+                if !state.set_gamma(output, Some(ramps)) {
+                    control.failed();
+                    state.gamma_control_manager_state().remove(control);
+                    state.set_gamma(output, None);
+                }
+            }
+            zwlr_gamma_control_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        control: &ZwlrGammaControlV1,
+        output: &Option<Output>,
+    ) {
+        // also called for every control of a client that disconnected
+        if state.gamma_control_manager_state().remove(control) {
+            if let Some(output) = output {
+                state.set_gamma(output, None);
+            }
+        }
+    }
+}
+
+/// Handler trait for wlr-gamma-control.
+pub trait GammaControlHandler {
+    fn gamma_control_manager_state(&mut self) -> &mut GammaControlManagerState;
+
+    /// Number of entries of each gamma ramp of `output`, `None` if it has none.
+    fn gamma_size(&self, output: &Output) -> Option<u32>;
+
+    /// Apply the red, green and blue ramps one after another, or restore the original
+    /// ones with `None`. Returns whether that succeeded.
+    fn set_gamma(&mut self, output: &Output, ramps: Option<Vec<u16>>) -> bool;
+}
+
+#[allow(missing_docs)]
+#[macro_export]
+macro_rules! delegate_gamma_control {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::protocols::gamma_control::GammaControlManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::protocols::gamma_control::GammaControlManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_v1::ZwlrGammaControlV1: Option<smithay::output::Output>
+        ] => $crate::protocols::gamma_control::GammaControlManagerState);
+    };
+}
@@ -1 +1,8 @@
+pub mod foreign_toplevel;
+pub mod foreign_toplevel_list;
+pub mod gamma_control;
+pub mod output_management;
+pub mod output_power_management;
 pub mod screencopy;
+pub mod tearing_control;
+pub mod virtual_input;
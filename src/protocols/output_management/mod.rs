@@ -0,0 +1,736 @@
+//! wlr-output-management protocol.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+use _output_management::{
+    zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
+    zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+    zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+    zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+    zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+};
+use smithay::output::{Mode, Output};
+use smithay::reexports::wayland_protocols_wlr::output_management::v1::server as _output_management;
+use smithay::reexports::wayland_server::{
+    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    WEnum,
+};
+use smithay::utils::{Logical, Physical, Point, Size, Transform};
+
+const MANAGER_VERSION: u32 = 4;
+
+/// Mode requested for a head in an output configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModeConfiguration {
+    /// One of the modes advertised for the head.
+    Mode(Mode),
+    /// A mode not advertised for the head, refresh rate in mHz.
+    Custom {
+        size: Size<i32, Physical>,
+        refresh: Option<i32>,
+    },
+}
+
+/// Requested state of a single enabled head.
+#[derive(Debug, Clone)]
+pub struct HeadConfiguration {
+    pub output: Output,
+    pub mode: Option<ModeConfiguration>,
+    pub position: Option<Point<i32, Logical>>,
+    pub transform: Option<Transform>,
+    pub scale: Option<f64>,
+    pub adaptive_sync: Option<bool>,
+}
+
+impl HeadConfiguration {
+    fn new(output: Output) -> Self {
+        HeadConfiguration {
+            output,
+            mode: None,
+            position: None,
+            transform: None,
+            scale: None,
+            adaptive_sync: None,
+        }
+    }
+}
+
+struct OutputManager {
+    manager: ZwlrOutputManagerV1,
+    heads: Vec<OutputHead>,
+}
+
+struct OutputHead {
+    head: ZwlrOutputHeadV1,
+    modes: Vec<ZwlrOutputModeV1>,
+}
+
+impl OutputHead {
+    fn output(&self) -> &Output {
+        self.head.data::<Output>().unwrap()
+    }
+}
+
+// State of an output that is not part of `Output` itself.
+struct HeadState {
+    output: Output,
+    enabled: bool,
+    adaptive_sync: bool,
+}
+
+pub struct OutputManagementState {
+    dh: DisplayHandle,
+    serial: u32,
+    outputs: Vec<HeadState>,
+    managers: Vec<OutputManager>,
+}
+
+impl OutputManagementState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwlrOutputManagerV1, ()>,
+        D: Dispatch<ZwlrOutputManagerV1, ()>,
+        D: Dispatch<ZwlrOutputHeadV1, Output>,
+        D: Dispatch<ZwlrOutputModeV1, Mode>,
+        D: Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>,
+        D: Dispatch<ZwlrOutputConfigurationHeadV1, Mutex<HeadConfiguration>>,
+        D: OutputManagementHandler,
+        D: 'static,
+    {
+        display.create_global::<D, ZwlrOutputManagerV1, _>(MANAGER_VERSION, ());
+
+        Self {
+            dh: display.clone(),
+            serial: 0,
+            outputs: Vec::new(),
+            managers: Vec::new(),
+        }
+    }
+
+    /// Advertise a new, enabled output to all bound clients.
+    pub fn add_head<D>(&mut self, output: &Output, adaptive_sync: bool)
+    where
+        D: Dispatch<ZwlrOutputHeadV1, Output>,
+        D: Dispatch<ZwlrOutputModeV1, Mode>,
+        D: 'static,
+    {
+        if self.outputs.iter().any(|state| &state.output == output) {
+            return;
+        }
+        let state = HeadState {
+            output: output.clone(),
+            enabled: true,
+            adaptive_sync,
+        };
+        for manager in &mut self.managers {
+            if let Some(head) = send_head::<D>(&self.dh, &manager.manager, &state) {
+                manager.heads.push(head);
+            }
+        }
+        self.outputs.push(state);
+        self.done();
+    }
+
+    /// Withdraw an output from all bound clients.
+    pub fn remove_head(&mut self, output: &Output) {
+        if !self.outputs.iter().any(|state| &state.output == output) {
+            return;
+        }
+        self.outputs.retain(|state| &state.output != output);
+        for manager in &mut self.managers {
+            manager.heads.retain(|head| {
+                if head.output() != output {
+                    return true;
+                }
+                for mode in &head.modes {
+                    mode.finished();
+                }
+                head.head.finished();
+                false
+            });
+        }
+        self.done();
+    }
+
+    /// Resend the current state of every output, e.g. after it was reconfigured.
+    pub fn update<D>(&mut self)
+    where
+        D: Dispatch<ZwlrOutputModeV1, Mode>,
+        D: 'static,
+    {
+        for manager in &mut self.managers {
+            for head in &mut manager.heads {
+                send_new_modes::<D>(&self.dh, head);
+                if let Some(state) = self.outputs.iter().find(|s| &s.output == head.output()) {
+                    send_head_state(head, state);
+                }
+            }
+        }
+        self.done();
+    }
+
+    /// Report an output as enabled or disabled, e.g. after it was turned on or off.
+    pub fn set_head_enabled(&mut self, output: &Output, enabled: bool) {
+        self.change_head_state(output, |state| state.enabled = enabled);
+    }
+
+    /// Report whether adaptive sync is enabled on an output.
+    pub fn set_head_adaptive_sync(&mut self, output: &Output, adaptive_sync: bool) {
+        self.change_head_state(output, |state| state.adaptive_sync = adaptive_sync);
+    }
+
+    fn change_head_state(&mut self, output: &Output, change: impl FnOnce(&mut HeadState)) {
+        let Some(state) = self.outputs.iter_mut().find(|s| &s.output == output) else {
+            return;
+        };
+        let (enabled, adaptive_sync) = (state.enabled, state.adaptive_sync);
+        change(state);
+        if (enabled, adaptive_sync) == (state.enabled, state.adaptive_sync) {
+            return;
+        }
+        for manager in &self.managers {
+            for head in manager.heads.iter().filter(|head| head.output() == output) {
+                send_head_state(head, state);
+            }
+        }
+        self.done();
+    }
+
+    fn done(&mut self) {
+        self.serial = self.serial.wrapping_add(1);
+        for manager in &self.managers {
+            manager.manager.done(self.serial);
+        }
+    }
+}
+
+fn send_head<D>(
+    dh: &DisplayHandle,
+    manager: &ZwlrOutputManagerV1,
+    state: &HeadState,
+) -> Option<OutputHead>
+where
+    D: Dispatch<ZwlrOutputHeadV1, Output>,
+    D: Dispatch<ZwlrOutputModeV1, Mode>,
+    D: 'static,
+{
+    let output = &state.output;
+    let client = manager.client()?;
+    let head = client
+        .create_resource::<ZwlrOutputHeadV1, _, D>(dh, manager.version(), output.clone())
+        .ok()?;
+    manager.head(&head);
+
+    let physical = output.physical_properties();
+    head.name(output.name());
+    head.description(output.description());
+    head.physical_size(physical.size.w, physical.size.h);
+    if head.version() >= 2 {
+        head.make(physical.make);
+        head.model(physical.model);
+    }
+
+    let mut head = OutputHead {
+        head,
+        modes: Vec::new(),
+    };
+    send_new_modes::<D>(dh, &mut head);
+    send_head_state(&head, state);
+    Some(head)
+}
+
+// Advertises all modes of the output that the head does not know about yet.
+fn send_new_modes<D>(dh: &DisplayHandle, head: &mut OutputHead)
+where
+    D: Dispatch<ZwlrOutputModeV1, Mode>,
+    D: 'static,
+{
+    let client = match head.head.client() {
+        Some(client) => client,
+        None => return,
+    };
+    let output = head.output().clone();
+    let preferred = output.preferred_mode();
+    for mode in output.modes() {
+        if head
+            .modes
+            .iter()
+            .any(|wl_mode| wl_mode.data::<Mode>() == Some(&mode))
+        {
+            continue;
+        }
+        let wl_mode =
+            match client.create_resource::<ZwlrOutputModeV1, _, D>(dh, head.head.version(), mode) {
+                Ok(wl_mode) => wl_mode,
+                Err(_) => continue,
+            };
+        head.head.mode(&wl_mode);
+        wl_mode.size(mode.size.w, mode.size.h);
+        wl_mode.refresh(mode.refresh);
+        if preferred == Some(mode) {
+            wl_mode.preferred();
+        }
+        head.modes.push(wl_mode);
+    }
+}
+
+fn send_head_state(head: &OutputHead, state: &HeadState) {
+    let output = head.output();
+    if head.head.version() >= 4 {
+        head.head.adaptive_sync(if state.adaptive_sync {
+            zwlr_output_head_v1::AdaptiveSyncState::Enabled
+        } else {
+            zwlr_output_head_v1::AdaptiveSyncState::Disabled
+        });
+    }
+    head.head.enabled(state.enabled as i32);
+    // the rest of the state only applies to enabled heads
+    if !state.enabled {
+        return;
+    }
+    if let Some(current) = output.current_mode() {
+        if let Some(wl_mode) = head
+            .modes
+            .iter()
+            .find(|wl_mode| wl_mode.data::<Mode>() == Some(&current))
+        {
+            head.head.current_mode(wl_mode);
+        }
+    }
+    let location = output.current_location();
+    head.head.position(location.x, location.y);
+    head.head.transform(output.current_transform().into());
+    head.head.scale(output.current_scale().fractional_scale());
+}
+
+impl<D> GlobalDispatch<ZwlrOutputManagerV1, (), D> for OutputManagementState
+where
+    D: GlobalDispatch<ZwlrOutputManagerV1, ()>,
+    D: Dispatch<ZwlrOutputManagerV1, ()>,
+    D: Dispatch<ZwlrOutputHeadV1, Output>,
+    D: Dispatch<ZwlrOutputModeV1, Mode>,
+    D: Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>,
+    D: Dispatch<ZwlrOutputConfigurationHeadV1, Mutex<HeadConfiguration>>,
+    D: OutputManagementHandler,
+    D: 'static,
+{
+    fn bind(
+        state: &mut D,
+        display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ZwlrOutputManagerV1>,
+        _manager_state: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(manager, ());
+        let state = state.output_management_state();
+        let heads = state
+            .outputs
+            .iter()
+            .filter_map(|head| send_head::<D>(display, &manager, head))
+            .collect();
+        manager.done(state.serial);
+        state.managers.push(OutputManager { manager, heads });
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputManagerV1, (), D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputManagerV1, ()>,
+    D: Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>,
+    D: OutputManagementHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        manager: &ZwlrOutputManagerV1,
+        request: zwlr_output_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_manager_v1::Request::CreateConfiguration { id, serial } => {
+                data_init.init(
+                    id,
+                    OutputConfigurationData {
+                        serial,
+                        used: AtomicBool::new(false),
+                        heads: Mutex::new(Vec::new()),
+                        disabled: Mutex::new(Vec::new()),
+                    },
+                );
+            }
+            zwlr_output_manager_v1::Request::Stop => {
+                state
+                    .output_management_state()
+                    .managers
+                    .retain(|m| &m.manager != manager);
+                manager.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, manager: &ZwlrOutputManagerV1, _data: &()) {
+        state
+            .output_management_state()
+            .managers
+            .retain(|m| &m.manager != manager);
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputHeadV1, Output, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputHeadV1, Output>,
+    D: OutputManagementHandler,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _head: &ZwlrOutputHeadV1,
+        request: zwlr_output_head_v1::Request,
+        _data: &Output,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_head_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, head: &ZwlrOutputHeadV1, _data: &Output) {
+        for manager in &mut state.output_management_state().managers {
+            manager.heads.retain(|h| &h.head != head);
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputModeV1, Mode, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputModeV1, Mode>,
+    D: OutputManagementHandler,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _mode: &ZwlrOutputModeV1,
+        request: zwlr_output_mode_v1::Request,
+        _data: &Mode,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_mode_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _client: ClientId, mode: &ZwlrOutputModeV1, _data: &Mode) {
+        for manager in &mut state.output_management_state().managers {
+            for head in &mut manager.heads {
+                head.modes.retain(|m| m != mode);
+            }
+        }
+    }
+}
+
+/// Pending output configuration created by a client.
+pub struct OutputConfigurationData {
+    serial: u32,
+    used: AtomicBool,
+    heads: Mutex<Vec<ZwlrOutputConfigurationHeadV1>>,
+    disabled: Mutex<Vec<Output>>,
+}
+
+impl OutputConfigurationData {
+    fn configures(&self, output: &Output) -> bool {
+        self.disabled.lock().unwrap().contains(output)
+            || self
+                .heads
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|head| head.data::<Mutex<HeadConfiguration>>())
+                .any(|head| &head.lock().unwrap().output == output)
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputConfigurationV1, OutputConfigurationData>,
+    D: Dispatch<ZwlrOutputConfigurationHeadV1, Mutex<HeadConfiguration>>,
+    D: Dispatch<ZwlrOutputModeV1, Mode>,
+    D: OutputManagementHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        configuration: &ZwlrOutputConfigurationV1,
+        request: zwlr_output_configuration_v1::Request,
+        data: &OutputConfigurationData,
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_configuration_v1::Request::EnableHead { id, head } => {
+                let output = head.data::<Output>().unwrap().clone();
+                if data.configures(&output) {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::AlreadyConfiguredHead,
+                        "head has already been configured",
+                    );
+                    return;
+                }
+                let head = data_init.init(id, Mutex::new(HeadConfiguration::new(output)));
+                data.heads.lock().unwrap().push(head);
+            }
+            zwlr_output_configuration_v1::Request::DisableHead { head } => {
+                let output = head.data::<Output>().unwrap().clone();
+                if data.configures(&output) {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::AlreadyConfiguredHead,
+                        "head has already been configured",
+                    );
+                    return;
+                }
+                data.disabled.lock().unwrap().push(output);
+            }
+            zwlr_output_configuration_v1::Request::Apply
+            | zwlr_output_configuration_v1::Request::Test => {
+                let test_only = matches!(request, zwlr_output_configuration_v1::Request::Test);
+                if data.used.swap(true, Ordering::SeqCst) {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::AlreadyUsed,
+                        "configuration has already been applied or tested",
+                    );
+                    return;
+                }
+
+                let manager_state = state.output_management_state();
+                if manager_state
+                    .outputs
+                    .iter()
+                    .any(|state| !data.configures(&state.output))
+                {
+                    configuration.post_error(
+                        zwlr_output_configuration_v1::Error::UnconfiguredHead,
+                        "not all heads have been configured",
+                    );
+                    return;
+                }
+                if data.serial != manager_state.serial {
+                    configuration.cancelled();
+                    return;
+                }
+
+                let heads = data
+                    .heads
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter_map(|head| head.data::<Mutex<HeadConfiguration>>())
+                    .map(|head| head.lock().unwrap().clone())
+                    .collect();
+                let disabled = data.disabled.lock().unwrap().clone();
+                if state.apply_output_configuration(heads, disabled, test_only) {
+                    configuration.succeeded();
+                    if !test_only {
+                        state.output_management_state().update::<D>();
+                    }
+                } else {
+                    configuration.failed();
+                }
+            }
+            zwlr_output_configuration_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationHeadV1, Mutex<HeadConfiguration>, D>
+    for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputConfigurationHeadV1, Mutex<HeadConfiguration>>,
+    D: OutputManagementHandler,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        head: &ZwlrOutputConfigurationHeadV1,
+        request: zwlr_output_configuration_head_v1::Request,
+        data: &Mutex<HeadConfiguration>,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let mut pending = data.lock().unwrap();
+        match request {
+            zwlr_output_configuration_head_v1::Request::SetMode { mode } => {
+                if pending.mode.is_some() {
+                    head.post_error(
+                        zwlr_output_configuration_head_v1::Error::AlreadySet,
+                        "mode has already been set",
+                    );
+                    return;
+                }
+                match mode.data::<Mode>() {
+                    Some(mode) if pending.output.modes().contains(mode) => {
+                        pending.mode = Some(ModeConfiguration::Mode(*mode));
+                    }
+                    _ => head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidMode,
+                        "mode does not belong to this head",
+                    ),
+                }
+            }
+            zwlr_output_configuration_head_v1::Request::SetCustomMode {
+                width,
+                height,
+                refresh,
+            } => {
+                if pending.mode.is_some() {
+                    head.post_error(
+                        zwlr_output_configuration_head_v1::Error::AlreadySet,
+                        "mode has already been set",
+                    );
+                    return;
+                }
+                // larger sizes don't fit in a DRM mode
+                let max_size = i32::from(u16::MAX);
+                if width <= 0 || height <= 0 || width > max_size || height > max_size || refresh < 0
+                {
+                    head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidCustomMode,
+                        "invalid custom mode",
+                    );
+                    return;
+                }
+                pending.mode = Some(ModeConfiguration::Custom {
+                    size: (width, height).into(),
+                    refresh: (refresh > 0).then_some(refresh),
+                });
+            }
+            zwlr_output_configuration_head_v1::Request::SetPosition { x, y } => {
+                if pending.position.is_some() {
+                    head.post_error(
+                        zwlr_output_configuration_head_v1::Error::AlreadySet,
+                        "position has already been set",
+                    );
+                    return;
+                }
+                pending.position = Some((x, y).into());
+            }
+            zwlr_output_configuration_head_v1::Request::SetTransform { transform } => {
+                if pending.transform.is_some() {
+                    head.post_error(
+                        zwlr_output_configuration_head_v1::Error::AlreadySet,
+                        "transform has already been set",
+                    );
+                    return;
+                }
+                match transform {
+                    WEnum::Value(transform) => pending.transform = Some(transform.into()),
+                    WEnum::Unknown(_) => head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidTransform,
+                        "invalid transform",
+                    ),
+                }
+            }
+            zwlr_output_configuration_head_v1::Request::SetScale { scale } => {
+                if pending.scale.is_some() {
+                    head.post_error(
+                        zwlr_output_configuration_head_v1::Error::AlreadySet,
+                        "scale has already been set",
+                    );
+                    return;
+                }
+                // also rejects NaN
+                if !(scale > 0.0 && scale.is_finite()) {
+                    head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidScale,
+                        "scale must be positive",
+                    );
+                    return;
+                }
+                pending.scale = Some(scale);
+            }
+            zwlr_output_configuration_head_v1::Request::SetAdaptiveSync { state } => {
+                if pending.adaptive_sync.is_some() {
+                    head.post_error(
+                        zwlr_output_configuration_head_v1::Error::AlreadySet,
+                        "adaptive sync has already been set",
+                    );
+                    return;
+                }
+                match state {
+                    WEnum::Value(zwlr_output_head_v1::AdaptiveSyncState::Enabled) => {
+                        pending.adaptive_sync = Some(true)
+                    }
+                    WEnum::Value(zwlr_output_head_v1::AdaptiveSyncState::Disabled) => {
+                        pending.adaptive_sync = Some(false)
+                    }
+                    _ => head.post_error(
+                        zwlr_output_configuration_head_v1::Error::InvalidAdaptiveSyncState,
+                        "invalid adaptive sync state",
+                    ),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Handler trait for wlr-output-management.
+pub trait OutputManagementHandler {
+    fn output_management_state(&mut self) -> &mut OutputManagementState;
+
+    /// Apply or only test a new configuration, enabling `heads` and disabling the
+    /// `disabled` outputs.
+    ///
+    /// Returns whether the configuration was (or could be) applied.
+    fn apply_output_configuration(
+        &mut self,
+        heads: Vec<HeadConfiguration>,
+        disabled: Vec<Output>,
+        test_only: bool,
+    ) -> bool;
+}
+
+#[allow(missing_docs)]
+#[macro_export]
+macro_rules! delegate_output_management {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: ()
+        ] => $crate::protocols::output_management::OutputManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: ()
+        ] => $crate::protocols::output_management::OutputManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_head_v1::ZwlrOutputHeadV1: smithay::output::Output
+        ] => $crate::protocols::output_management::OutputManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_mode_v1::ZwlrOutputModeV1: smithay::output::Mode
+        ] => $crate::protocols::output_management::OutputManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1: $crate::protocols::output_management::OutputConfigurationData
+        ] => $crate::protocols::output_management::OutputManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1: std::sync::Mutex<$crate::protocols::output_management::HeadConfiguration>
+        ] => $crate::protocols::output_management::OutputManagementState);
+    };
+}
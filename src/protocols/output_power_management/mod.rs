@@ -0,0 +1,197 @@
+//! wlr-output-power-management protocol.
+
+use _output_power::{
+    zwlr_output_power_manager_v1::{self, ZwlrOutputPowerManagerV1},
+    zwlr_output_power_v1::{self, Mode, ZwlrOutputPowerV1},
+};
+use smithay::output::Output;
+use smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server as _output_power;
+use smithay::reexports::wayland_server::{
+    backend::ClientId, Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+    WEnum,
+};
+
+const MANAGER_VERSION: u32 = 1;
+
+pub struct OutputPowerManagementState {
+    powers: Vec<ZwlrOutputPowerV1>,
+}
+
+impl OutputPowerManagementState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<ZwlrOutputPowerManagerV1, ()>,
+        D: Dispatch<ZwlrOutputPowerManagerV1, ()>,
+        D: Dispatch<ZwlrOutputPowerV1, Option<Output>>,
+        D: OutputPowerManagementHandler,
+        D: 'static,
+    {
+        display.create_global::<D, ZwlrOutputPowerManagerV1, _>(MANAGER_VERSION, ());
+
+        Self { powers: Vec::new() }
+    }
+
+    /// Tell clients that `output` was turned on or off.
+    pub fn mode_changed(&self, output: &Output, on: bool) {
+        for power in self.powers_for(output) {
+            power.mode(mode(on));
+        }
+    }
+
+    /// Invalidate the power objects of an output that was disconnected.
+    pub fn remove_output(&mut self, output: &Output) {
+        for power in self.powers_for(output) {
+            power.failed();
+        }
+        self.powers
+            .retain(|power| power.data::<Option<Output>>().unwrap().as_ref() != Some(output));
+    }
+
+    fn powers_for<'a>(&'a self, output: &'a Output) -> impl Iterator<Item = &'a ZwlrOutputPowerV1> {
+        self.powers
+            .iter()
+            .filter(move |power| power.data::<Option<Output>>().unwrap().as_ref() == Some(output))
+    }
+}
+
+fn mode(on: bool) -> Mode {
+    if on {
+        Mode::On
+    } else {
+        Mode::Off
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrOutputPowerManagerV1, (), D> for OutputPowerManagementState
+where
+    D: GlobalDispatch<ZwlrOutputPowerManagerV1, ()>,
+    D: Dispatch<ZwlrOutputPowerManagerV1, ()>,
+    D: Dispatch<ZwlrOutputPowerV1, Option<Output>>,
+    D: OutputPowerManagementHandler,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ZwlrOutputPowerManagerV1>,
+        _manager_state: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputPowerManagerV1, (), D> for OutputPowerManagementState
+where
+    D: Dispatch<ZwlrOutputPowerManagerV1, ()>,
+    D: Dispatch<ZwlrOutputPowerV1, Option<Output>>,
+    D: OutputPowerManagementHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        _manager: &ZwlrOutputPowerManagerV1,
+        request: zwlr_output_power_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_power_manager_v1::Request::GetOutputPower { id, output } => {
+                let output = Output::from_resource(&output);
+                let power = data_init.init(id, output.clone());
+                match output {
+                    Some(output) => {
+                        power.mode(mode(state.output_powered(&output)));
+                        state.output_power_management_state().powers.push(power);
+                    }
+                    // the output is already gone
+                    None => power.failed(),
+                }
+            }
+            zwlr_output_power_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputPowerV1, Option<Output>, D> for OutputPowerManagementState
+where
+    D: Dispatch<ZwlrOutputPowerV1, Option<Output>>,
+    D: OutputPowerManagementHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        power: &ZwlrOutputPowerV1,
+        request: zwlr_output_power_v1::Request,
+        output: &Option<Output>,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_power_v1::Request::SetMode { mode } => {
+                let on = match mode {
+                    WEnum::Value(Mode::On) => true,
+                    WEnum::Value(Mode::Off) => false,
+                    _ => {
+                        power.post_error(
+                            zwlr_output_power_v1::Error::InvalidMode,
+                            "invalid power mode",
+                        );
+                        return;
+                    }
+                };
+                if let Some(output) = output {
+                    state.set_output_powered(output, on);
+                }
+            }
+            zwlr_output_power_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        power: &ZwlrOutputPowerV1,
+        _data: &Option<Output>,
+    ) {
+        state
+            .output_power_management_state()
+            .powers
+            .retain(|p| p != power);
+    }
+}
+
+/// Handler trait for wlr-output-power-management.
+pub trait OutputPowerManagementHandler {
+    fn output_power_management_state(&mut self) -> &mut OutputPowerManagementState;
+
+    /// Whether `output` is currently turned on.
+    fn output_powered(&self, output: &Output) -> bool;
+
+    /// Turn `output` on or off, calling [`OutputPowerManagementState::mode_changed`] on success.
+    fn set_output_powered(&mut self, output: &Output, on: bool);
+}
+
+#[allow(missing_docs)]
+#[macro_export]
+macro_rules! delegate_output_power_management {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1: ()
+        ] => $crate::protocols::output_power_management::OutputPowerManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_manager_v1::ZwlrOutputPowerManagerV1: ()
+        ] => $crate::protocols::output_power_management::OutputPowerManagementState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::output_power_management::v1::server::zwlr_output_power_v1::ZwlrOutputPowerV1: Option<smithay::output::Output>
+        ] => $crate::protocols::output_power_management::OutputPowerManagementState);
+    };
+}
@@ -0,0 +1,175 @@
+//! wp-tearing-control protocol.
+
+use std::sync::Mutex;
+
+use _tearing_control::{
+    wp_tearing_control_manager_v1::{self, WpTearingControlManagerV1},
+    wp_tearing_control_v1::{self, PresentationHint, WpTearingControlV1},
+};
+use smithay::reexports::wayland_protocols::wp::tearing_control::v1::server as _tearing_control;
+use smithay::reexports::wayland_server::{
+    backend::ClientId, protocol::wl_surface::WlSurface, Client, DataInit, Dispatch, DisplayHandle,
+    GlobalDispatch, New, Resource, WEnum,
+};
+use smithay::wayland::compositor::{with_states, SurfaceData};
+
+const MANAGER_VERSION: u32 = 1;
+
+// the hint of a surface, `None` while it has no tearing control object
+#[derive(Default)]
+struct TearingHint(Mutex<Option<PresentationHint>>);
+
+/// Whether the client asked for the surface to be presented as soon as possible, even if it tears.
+pub fn wants_tearing(states: &SurfaceData) -> bool {
+    states
+        .data_map
+        .get::<TearingHint>()
+        .is_some_and(|hint| *hint.0.lock().unwrap() == Some(PresentationHint::Async))
+}
+
+fn set_hint(surface: &WlSurface, hint: Option<PresentationHint>) {
+    if !surface.is_alive() {
+        return;
+    }
+    with_states(surface, |states| {
+        states
+            .data_map
+            .insert_if_missing_threadsafe(TearingHint::default);
+        *states
+            .data_map
+            .get::<TearingHint>()
+            .unwrap()
+            .0
+            .lock()
+            .unwrap() = hint;
+    });
+}
+
+pub struct TearingControlManagerState;
+
+impl TearingControlManagerState {
+    pub fn new<D>(display: &DisplayHandle) -> Self
+    where
+        D: GlobalDispatch<WpTearingControlManagerV1, ()>,
+        D: Dispatch<WpTearingControlManagerV1, ()>,
+        D: Dispatch<WpTearingControlV1, WlSurface>,
+        D: 'static,
+    {
+        display.create_global::<D, WpTearingControlManagerV1, _>(MANAGER_VERSION, ());
+
+        Self
+    }
+}
+
+impl<D> GlobalDispatch<WpTearingControlManagerV1, (), D> for TearingControlManagerState
+where
+    D: GlobalDispatch<WpTearingControlManagerV1, ()>,
+    D: Dispatch<WpTearingControlManagerV1, ()>,
+    D: Dispatch<WpTearingControlV1, WlSurface>,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _display: &DisplayHandle,
+        _client: &Client,
+        manager: New<WpTearingControlManagerV1>,
+        _manager_state: &(),
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+}
+
+impl<D> Dispatch<WpTearingControlManagerV1, (), D> for TearingControlManagerState
+where
+    D: Dispatch<WpTearingControlManagerV1, ()>,
+    D: Dispatch<WpTearingControlV1, WlSurface>,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        manager: &WpTearingControlManagerV1,
+        request: wp_tearing_control_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            wp_tearing_control_manager_v1::Request::GetTearingControl { id, surface } => {
+                let exists = with_states(&surface, |states| {
+                    states
+                        .data_map
+                        .get::<TearingHint>()
+                        .is_some_and(|hint| hint.0.lock().unwrap().is_some())
+                });
+                if exists {
+                    manager.post_error(
+                        wp_tearing_control_manager_v1::Error::TearingControlExists,
+                        "surface already has a tearing control object",
+                    );
+                    return;
+                }
+                set_hint(&surface, Some(PresentationHint::Vsync));
+                data_init.init(id, surface);
+            }
+            wp_tearing_control_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<WpTearingControlV1, WlSurface, D> for TearingControlManagerState
+where
+    D: Dispatch<WpTearingControlV1, WlSurface>,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _control: &WpTearingControlV1,
+        request: wp_tearing_control_v1::Request,
+        surface: &WlSurface,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            // takes effect right away instead of on the next commit, the hint is only
+            // looked at when the next frame is rendered anyway
+            wp_tearing_control_v1::Request::SetPresentationHint { hint } => {
+                if let WEnum::Value(hint) = hint {
+                    set_hint(surface, Some(hint));
+                }
+            }
+            wp_tearing_control_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        _state: &mut D,
+        _client: ClientId,
+        _control: &WpTearingControlV1,
+        surface: &WlSurface,
+    ) {
+        set_hint(surface, None);
+    }
+}
+
+#[allow(missing_docs)]
+#[macro_export]
+macro_rules! delegate_tearing_control {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::tearing_control::v1::server::wp_tearing_control_manager_v1::WpTearingControlManagerV1: ()
+        ] => $crate::protocols::tearing_control::TearingControlManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::tearing_control::v1::server::wp_tearing_control_manager_v1::WpTearingControlManagerV1: ()
+        ] => $crate::protocols::tearing_control::TearingControlManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols::wp::tearing_control::v1::server::wp_tearing_control_v1::WpTearingControlV1: smithay::reexports::wayland_server::protocol::wl_surface::WlSurface
+        ] => $crate::protocols::tearing_control::TearingControlManagerState);
+    };
+}
@@ -0,0 +1,345 @@
+//! virtual-keyboard protocol.
+//!
+//! The seat keyboard switches to the keymap uploaded by a client while its keys are
+//! processed, modifier requests are turned into presses of the keymap's modifier keys.
+
+use std::{
+    fs::File,
+    os::{fd::OwnedFd, unix::fs::FileExt},
+    sync::{Arc, Mutex},
+};
+
+use _virtual_keyboard::{
+    zwp_virtual_keyboard_manager_v1::{self, ZwpVirtualKeyboardManagerV1},
+    zwp_virtual_keyboard_v1::{self, ZwpVirtualKeyboardV1},
+};
+use smithay::backend::input::{InputEvent, KeyState};
+use smithay::input::keyboard::xkb;
+use smithay::reexports::wayland_protocols_misc::zwp_virtual_keyboard_v1::server as _virtual_keyboard;
+use smithay::reexports::wayland_server::{
+    backend::ClientId, protocol::wl_keyboard, Client, DataInit, Dispatch, DisplayHandle,
+    GlobalDispatch, New, Resource,
+};
+use tracing::warn;
+
+use super::{VirtualDevice, VirtualInputGlobalData, VirtualInputHandler, VirtualKeyEvent};
+
+const MANAGER_VERSION: u32 = 1;
+
+/// Largest keymap accepted from a client.
+const MAX_KEYMAP_SIZE: u32 = 1024 * 1024;
+
+pub struct VirtualKeyboardData {
+    device: VirtualDevice,
+    state: Mutex<KeyboardState>,
+}
+
+#[derive(Default)]
+struct KeyboardState {
+    keymap: Option<VirtualKeymap>,
+    // released when the keyboard goes away
+    pressed: Vec<u32>,
+    time: u32,
+    // modifiers locked by modifiers requests
+    locked: u32,
+}
+
+struct VirtualKeymap {
+    keymap: Arc<str>,
+    modifiers: Vec<ModifierKey>,
+}
+
+// a key setting or locking a single modifier
+struct ModifierKey {
+    mask: u32,
+    key: u32,
+    locks: bool,
+}
+
+fn load_keymap(format: u32, fd: OwnedFd, size: u32) -> Option<VirtualKeymap> {
+    if format != wl_keyboard::KeymapFormat::XkbV1 as u32 || size > MAX_KEYMAP_SIZE {
+        return None;
+    }
+    let mut bytes = vec![0; size as usize];
+    // from the start, the client leaves the offset behind what it wrote
+    if let Err(err) = File::from(fd).read_exact_at(&mut bytes, 0) {
+        warn!("Failed to read the keymap of a virtual keyboard: {}", err);
+        return None;
+    }
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    bytes.truncate(len);
+    let keymap = xkb::Keymap::new_from_string(
+        &xkb::Context::new(xkb::CONTEXT_NO_FLAGS),
+        String::from_utf8(bytes).ok()?,
+        xkb::KEYMAP_FORMAT_TEXT_V1,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )?;
+    Some(VirtualKeymap {
+        modifiers: modifier_keys(&keymap),
+        keymap: keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1).into(),
+    })
+}
+
+fn modifier_keys(keymap: &xkb::Keymap) -> Vec<ModifierKey> {
+    let mut keys: Vec<ModifierKey> = Vec::new();
+    for keycode in keymap.min_keycode().raw()..=keymap.max_keycode().raw() {
+        let mut state = xkb::State::new(keymap);
+        state.update_key(xkb::Keycode::new(keycode), xkb::KeyDirection::Down);
+        let depressed = state.serialize_mods(xkb::STATE_MODS_DEPRESSED);
+        state.update_key(xkb::Keycode::new(keycode), xkb::KeyDirection::Up);
+        let locked = state.serialize_mods(xkb::STATE_MODS_LOCKED);
+        let (mask, locks) = if locked.count_ones() == 1 {
+            (locked, true)
+        } else if depressed.count_ones() == 1 && locked == 0 {
+            (depressed, false)
+        } else {
+            continue;
+        };
+        if !keys.iter().any(|k| k.mask == mask && k.locks == locks) {
+            keys.push(ModifierKey {
+                mask,
+                // evdev codes are sent by the protocol
+                key: keycode - 8,
+                locks,
+            });
+        }
+    }
+    keys
+}
+
+impl VirtualKeyboardData {
+    // press or release a key, ignoring repeated presses and releases of keys that are not pressed
+    fn key<D: VirtualInputHandler>(&self, state: &mut D, time: u32, key: u32, key_state: KeyState) {
+        let keymap = {
+            let mut keyboard_state = self.state.lock().unwrap();
+            keyboard_state.time = time;
+            let pressed = keyboard_state.pressed.contains(&key);
+            match key_state {
+                KeyState::Pressed if !pressed => keyboard_state.pressed.push(key),
+                KeyState::Released if pressed => keyboard_state.pressed.retain(|k| *k != key),
+                _ => return,
+            }
+            match &keyboard_state.keymap {
+                Some(keymap) => keymap.keymap.clone(),
+                None => return,
+            }
+        };
+        state.virtual_input(InputEvent::Keyboard {
+            event: VirtualKeyEvent {
+                device: self.device.clone(),
+                keymap,
+                time,
+                key,
+                state: key_state,
+            },
+        });
+    }
+
+    fn set_modifiers<D: VirtualInputHandler>(&self, state: &mut D, depressed: u32, locked: u32) {
+        let mut keys = Vec::new();
+        let time = {
+            let mut keyboard_state = self.state.lock().unwrap();
+            let Some(keymap) = &keyboard_state.keymap else {
+                return;
+            };
+            for modifier in &keymap.modifiers {
+                if modifier.locks {
+                    if (locked ^ keyboard_state.locked) & modifier.mask != 0 {
+                        keys.push((modifier.key, KeyState::Pressed));
+                        keys.push((modifier.key, KeyState::Released));
+                    }
+                } else {
+                    let pressed = keyboard_state.pressed.contains(&modifier.key);
+                    if pressed != (depressed & modifier.mask != 0) {
+                        keys.push((
+                            modifier.key,
+                            if pressed {
+                                KeyState::Released
+                            } else {
+                                KeyState::Pressed
+                            },
+                        ));
+                    }
+                }
+            }
+            keyboard_state.locked = locked;
+            keyboard_state.time
+        };
+        for (key, key_state) in keys {
+            self.key(state, time, key, key_state);
+        }
+    }
+}
+
+pub struct VirtualKeyboardManagerState;
+
+impl VirtualKeyboardManagerState {
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwpVirtualKeyboardManagerV1, VirtualInputGlobalData>,
+        D: Dispatch<ZwpVirtualKeyboardManagerV1, ()>,
+        D: Dispatch<ZwpVirtualKeyboardV1, VirtualKeyboardData>,
+        D: VirtualInputHandler,
+        D: 'static,
+        F: for<'c> Fn(&'c Client) -> bool + Send + Sync + 'static,
+    {
+        let data = VirtualInputGlobalData {
+            filter: Box::new(filter),
+        };
+        display.create_global::<D, ZwpVirtualKeyboardManagerV1, _>(MANAGER_VERSION, data);
+
+        Self
+    }
+}
+
+impl<D> GlobalDispatch<ZwpVirtualKeyboardManagerV1, VirtualInputGlobalData, D>
+    for VirtualKeyboardManagerState
+where
+    D: GlobalDispatch<ZwpVirtualKeyboardManagerV1, VirtualInputGlobalData>,
+    D: Dispatch<ZwpVirtualKeyboardManagerV1, ()>,
+    D: Dispatch<ZwpVirtualKeyboardV1, VirtualKeyboardData>,
+    D: VirtualInputHandler,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ZwpVirtualKeyboardManagerV1>,
+        _manager_state: &VirtualInputGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+
+    fn can_view(client: Client, global_data: &VirtualInputGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwpVirtualKeyboardManagerV1, (), D> for VirtualKeyboardManagerState
+where
+    D: Dispatch<ZwpVirtualKeyboardManagerV1, ()>,
+    D: Dispatch<ZwpVirtualKeyboardV1, VirtualKeyboardData>,
+    D: VirtualInputHandler,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _manager: &ZwpVirtualKeyboardManagerV1,
+        request: zwp_virtual_keyboard_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            // there is only one seat
+            zwp_virtual_keyboard_manager_v1::Request::CreateVirtualKeyboard { seat: _, id } => {
+                data_init.init(
+                    id,
+                    VirtualKeyboardData {
+                        device: VirtualDevice::new(true),
+                        state: Mutex::new(KeyboardState::default()),
+                    },
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwpVirtualKeyboardV1, VirtualKeyboardData, D> for VirtualKeyboardManagerState
+where
+    D: Dispatch<ZwpVirtualKeyboardV1, VirtualKeyboardData>,
+    D: VirtualInputHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        keyboard: &ZwpVirtualKeyboardV1,
+        request: zwp_virtual_keyboard_v1::Request,
+        data: &VirtualKeyboardData,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwp_virtual_keyboard_v1::Request::Keymap { format, fd, size } => {
+                match load_keymap(format, fd, size) {
+                    Some(keymap) => data.state.lock().unwrap().keymap = Some(keymap),
+                    None => warn!("Ignoring invalid keymap of a virtual keyboard"),
+                }
+            }
+            zwp_virtual_keyboard_v1::Request::Key {
+                time,
+                key,
+                state: key_state,
+            } => {
+                if data.state.lock().unwrap().keymap.is_none() {
+                    keyboard.post_error(
+                        zwp_virtual_keyboard_v1::Error::NoKeymap,
+                        "key sent before a keymap",
+                    );
+                    return;
+                }
+                let key_state = if key_state == 1 {
+                    KeyState::Pressed
+                } else {
+                    KeyState::Released
+                };
+                data.key(state, time, key, key_state);
+            }
+            // latched modifiers and the layout group can't be set through key presses
+            zwp_virtual_keyboard_v1::Request::Modifiers {
+                mods_depressed,
+                mods_locked,
+                ..
+            } => {
+                if data.state.lock().unwrap().keymap.is_none() {
+                    keyboard.post_error(
+                        zwp_virtual_keyboard_v1::Error::NoKeymap,
+                        "modifiers sent before a keymap",
+                    );
+                    return;
+                }
+                data.set_modifiers(state, mods_depressed, mods_locked);
+            }
+            zwp_virtual_keyboard_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        _keyboard: &ZwpVirtualKeyboardV1,
+        data: &VirtualKeyboardData,
+    ) {
+        let (pressed, time) = {
+            let keyboard_state = data.state.lock().unwrap();
+            (keyboard_state.pressed.clone(), keyboard_state.time)
+        };
+        for key in pressed {
+            data.key(state, time, key, KeyState::Released);
+        }
+    }
+}
+
+#[allow(missing_docs)]
+#[macro_export]
+macro_rules! delegate_virtual_keyboard {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_misc::zwp_virtual_keyboard_v1::server::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1: $crate::protocols::virtual_input::VirtualInputGlobalData
+        ] => $crate::protocols::virtual_input::keyboard::VirtualKeyboardManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_misc::zwp_virtual_keyboard_v1::server::zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1: ()
+        ] => $crate::protocols::virtual_input::keyboard::VirtualKeyboardManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_misc::zwp_virtual_keyboard_v1::server::zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1: $crate::protocols::virtual_input::keyboard::VirtualKeyboardData
+        ] => $crate::protocols::virtual_input::keyboard::VirtualKeyboardManagerState);
+    };
+}
@@ -0,0 +1,305 @@
+//! Input devices emulated by clients, through virtual-keyboard and wlr-virtual-pointer.
+//!
+//! Their requests are turned into [`InputEvent`]s of the [`VirtualInput`] backend, so they are
+//! handled like events of real devices, except that keys don't trigger compositor keybindings.
+
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use smithay::backend::input::{
+    AbsolutePositionEvent, Axis, AxisSource, ButtonState, Device, DeviceCapability, Event,
+    InputBackend, InputEvent, KeyState, KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent,
+    PointerMotionAbsoluteEvent, PointerMotionEvent, UnusedEvent,
+};
+use smithay::reexports::wayland_server::Client;
+
+pub mod keyboard;
+pub mod pointer;
+
+/// Global data of the virtual input managers, deciding which clients can see them.
+pub struct VirtualInputGlobalData {
+    filter: Box<dyn for<'c> Fn(&'c Client) -> bool + Send + Sync>,
+}
+
+/// Handler trait for virtual input devices.
+pub trait VirtualInputHandler {
+    /// Handle an event emulated by a client.
+    fn virtual_input(&mut self, event: InputEvent<VirtualInput>);
+}
+
+/// Input backend of the devices created by clients.
+#[derive(Debug)]
+pub struct VirtualInput;
+
+impl InputBackend for VirtualInput {
+    type Device = VirtualDevice;
+    type KeyboardKeyEvent = VirtualKeyEvent;
+    type PointerAxisEvent = VirtualAxisEvent;
+    type PointerButtonEvent = VirtualButtonEvent;
+    type PointerMotionEvent = VirtualMotionEvent;
+    type PointerMotionAbsoluteEvent = VirtualMotionAbsoluteEvent;
+    type GestureSwipeBeginEvent = UnusedEvent;
+    type GestureSwipeUpdateEvent = UnusedEvent;
+    type GestureSwipeEndEvent = UnusedEvent;
+    type GesturePinchBeginEvent = UnusedEvent;
+    type GesturePinchUpdateEvent = UnusedEvent;
+    type GesturePinchEndEvent = UnusedEvent;
+    type GestureHoldBeginEvent = UnusedEvent;
+    type GestureHoldEndEvent = UnusedEvent;
+    type TouchDownEvent = UnusedEvent;
+    type TouchUpEvent = UnusedEvent;
+    type TouchMotionEvent = UnusedEvent;
+    type TouchCancelEvent = UnusedEvent;
+    type TouchFrameEvent = UnusedEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+    type SpecialEvent = UnusedEvent;
+}
+
+/// A virtual keyboard or pointer.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VirtualDevice {
+    id: u32,
+    keyboard: bool,
+}
+
+impl VirtualDevice {
+    fn new(keyboard: bool) -> Self {
+        static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            keyboard,
+        }
+    }
+}
+
+impl Device for VirtualDevice {
+    fn id(&self) -> String {
+        format!("virtual-{}", self.id)
+    }
+
+    fn name(&self) -> String {
+        if self.keyboard {
+            "virtual keyboard".to_string()
+        } else {
+            "virtual pointer".to_string()
+        }
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        match capability {
+            DeviceCapability::Keyboard => self.keyboard,
+            DeviceCapability::Pointer => !self.keyboard,
+            _ => false,
+        }
+    }
+
+    fn usb_id(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    fn syspath(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+// the protocols send milliseconds, events carry microseconds
+fn usec(time: u32) -> u64 {
+    time as u64 * 1000
+}
+
+#[derive(Debug)]
+pub struct VirtualKeyEvent {
+    device: VirtualDevice,
+    keymap: Arc<str>,
+    time: u32,
+    key: u32,
+    state: KeyState,
+}
+
+impl VirtualKeyEvent {
+    /// Keymap of the virtual keyboard, the key code is to be interpreted with.
+    pub fn keymap(&self) -> &Arc<str> {
+        &self.keymap
+    }
+}
+
+impl Event<VirtualInput> for VirtualKeyEvent {
+    fn time(&self) -> u64 {
+        usec(self.time)
+    }
+
+    fn device(&self) -> VirtualDevice {
+        self.device.clone()
+    }
+}
+
+impl KeyboardKeyEvent<VirtualInput> for VirtualKeyEvent {
+    fn key_code(&self) -> u32 {
+        self.key
+    }
+
+    fn state(&self) -> KeyState {
+        self.state
+    }
+
+    fn count(&self) -> u32 {
+        match self.state {
+            KeyState::Pressed => 1,
+            KeyState::Released => 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct VirtualMotionEvent {
+    device: VirtualDevice,
+    time: u32,
+    dx: f64,
+    dy: f64,
+}
+
+impl Event<VirtualInput> for VirtualMotionEvent {
+    fn time(&self) -> u64 {
+        usec(self.time)
+    }
+
+    fn device(&self) -> VirtualDevice {
+        self.device.clone()
+    }
+}
+
+impl PointerMotionEvent<VirtualInput> for VirtualMotionEvent {
+    fn delta_x(&self) -> f64 {
+        self.dx
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.dy
+    }
+
+    fn delta_x_unaccel(&self) -> f64 {
+        self.dx
+    }
+
+    fn delta_y_unaccel(&self) -> f64 {
+        self.dy
+    }
+}
+
+#[derive(Debug)]
+pub struct VirtualMotionAbsoluteEvent {
+    device: VirtualDevice,
+    time: u32,
+    x: u32,
+    y: u32,
+    x_extent: u32,
+    y_extent: u32,
+}
+
+impl Event<VirtualInput> for VirtualMotionAbsoluteEvent {
+    fn time(&self) -> u64 {
+        usec(self.time)
+    }
+
+    fn device(&self) -> VirtualDevice {
+        self.device.clone()
+    }
+}
+
+impl AbsolutePositionEvent<VirtualInput> for VirtualMotionAbsoluteEvent {
+    fn x(&self) -> f64 {
+        self.x as f64
+    }
+
+    fn y(&self) -> f64 {
+        self.y as f64
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        self.x as f64 * width as f64 / self.x_extent as f64
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        self.y as f64 * height as f64 / self.y_extent as f64
+    }
+}
+
+impl PointerMotionAbsoluteEvent<VirtualInput> for VirtualMotionAbsoluteEvent {}
+
+#[derive(Debug)]
+pub struct VirtualButtonEvent {
+    device: VirtualDevice,
+    time: u32,
+    button: u32,
+    state: ButtonState,
+}
+
+impl Event<VirtualInput> for VirtualButtonEvent {
+    fn time(&self) -> u64 {
+        usec(self.time)
+    }
+
+    fn device(&self) -> VirtualDevice {
+        self.device.clone()
+    }
+}
+
+impl PointerButtonEvent<VirtualInput> for VirtualButtonEvent {
+    fn button_code(&self) -> u32 {
+        self.button
+    }
+
+    fn state(&self) -> ButtonState {
+        self.state
+    }
+}
+
+/// Scrolling on both axes, collected until the frame request of the pointer.
+#[derive(Debug)]
+pub struct VirtualAxisEvent {
+    device: VirtualDevice,
+    time: u32,
+    source: Option<AxisSource>,
+    horizontal: Option<f64>,
+    vertical: Option<f64>,
+    horizontal_v120: Option<f64>,
+    vertical_v120: Option<f64>,
+}
+
+impl Event<VirtualInput> for VirtualAxisEvent {
+    fn time(&self) -> u64 {
+        usec(self.time)
+    }
+
+    fn device(&self) -> VirtualDevice {
+        self.device.clone()
+    }
+}
+
+impl PointerAxisEvent<VirtualInput> for VirtualAxisEvent {
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.horizontal,
+            Axis::Vertical => self.vertical,
+        }
+    }
+
+    fn amount_v120(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.horizontal_v120,
+            Axis::Vertical => self.vertical_v120,
+        }
+    }
+
+    fn source(&self) -> AxisSource {
+        self.source.unwrap_or(AxisSource::Wheel)
+    }
+}
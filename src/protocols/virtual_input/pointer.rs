@@ -0,0 +1,345 @@
+//! wlr-virtual-pointer protocol.
+
+use std::sync::Mutex;
+
+use _virtual_pointer::{
+    zwlr_virtual_pointer_manager_v1::{self, ZwlrVirtualPointerManagerV1},
+    zwlr_virtual_pointer_v1::{self, ZwlrVirtualPointerV1},
+};
+use smithay::backend::input::{Axis, AxisSource, ButtonState, InputEvent};
+use smithay::reexports::wayland_protocols_wlr::virtual_pointer::v1::server as _virtual_pointer;
+use smithay::reexports::wayland_server::{
+    backend::ClientId, protocol::wl_pointer, Client, DataInit, Dispatch, DisplayHandle,
+    GlobalDispatch, New, Resource, WEnum,
+};
+
+use super::{
+    VirtualAxisEvent, VirtualButtonEvent, VirtualDevice, VirtualInputGlobalData,
+    VirtualInputHandler, VirtualMotionAbsoluteEvent, VirtualMotionEvent,
+};
+
+const MANAGER_VERSION: u32 = 2;
+
+pub struct VirtualPointerData {
+    device: VirtualDevice,
+    state: Mutex<PointerState>,
+}
+
+#[derive(Default)]
+struct PointerState {
+    // scrolling sent since the last frame
+    axis: Option<VirtualAxisEvent>,
+    // released when the pointer goes away
+    pressed: Vec<u32>,
+    time: u32,
+}
+
+impl VirtualPointerData {
+    fn new() -> Self {
+        Self {
+            device: VirtualDevice::new(false),
+            state: Mutex::new(PointerState::default()),
+        }
+    }
+
+    fn with_axis(&self, time: Option<u32>, f: impl FnOnce(&mut VirtualAxisEvent)) {
+        let mut state = self.state.lock().unwrap();
+        let axis = state.axis.get_or_insert_with(|| VirtualAxisEvent {
+            device: self.device.clone(),
+            time: 0,
+            source: None,
+            horizontal: None,
+            vertical: None,
+            horizontal_v120: None,
+            vertical_v120: None,
+        });
+        if let Some(time) = time {
+            axis.time = time;
+        }
+        f(axis);
+    }
+}
+
+impl VirtualAxisEvent {
+    fn set(&mut self, axis: Axis, amount: f64, v120: Option<f64>) {
+        match axis {
+            Axis::Horizontal => {
+                self.horizontal = Some(amount);
+                self.horizontal_v120 = v120;
+            }
+            Axis::Vertical => {
+                self.vertical = Some(amount);
+                self.vertical_v120 = v120;
+            }
+        }
+    }
+}
+
+fn axis(pointer: &ZwlrVirtualPointerV1, axis: WEnum<wl_pointer::Axis>) -> Option<Axis> {
+    match axis {
+        WEnum::Value(wl_pointer::Axis::HorizontalScroll) => Some(Axis::Horizontal),
+        WEnum::Value(wl_pointer::Axis::VerticalScroll) => Some(Axis::Vertical),
+        _ => {
+            pointer.post_error(zwlr_virtual_pointer_v1::Error::InvalidAxis, "invalid axis");
+            None
+        }
+    }
+}
+
+pub struct VirtualPointerManagerState;
+
+impl VirtualPointerManagerState {
+    pub fn new<D, F>(display: &DisplayHandle, filter: F) -> Self
+    where
+        D: GlobalDispatch<ZwlrVirtualPointerManagerV1, VirtualInputGlobalData>,
+        D: Dispatch<ZwlrVirtualPointerManagerV1, ()>,
+        D: Dispatch<ZwlrVirtualPointerV1, VirtualPointerData>,
+        D: VirtualInputHandler,
+        D: 'static,
+        F: for<'c> Fn(&'c Client) -> bool + Send + Sync + 'static,
+    {
+        let data = VirtualInputGlobalData {
+            filter: Box::new(filter),
+        };
+        display.create_global::<D, ZwlrVirtualPointerManagerV1, _>(MANAGER_VERSION, data);
+
+        Self
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrVirtualPointerManagerV1, VirtualInputGlobalData, D>
+    for VirtualPointerManagerState
+where
+    D: GlobalDispatch<ZwlrVirtualPointerManagerV1, VirtualInputGlobalData>,
+    D: Dispatch<ZwlrVirtualPointerManagerV1, ()>,
+    D: Dispatch<ZwlrVirtualPointerV1, VirtualPointerData>,
+    D: VirtualInputHandler,
+    D: 'static,
+{
+    fn bind(
+        _state: &mut D,
+        _display: &DisplayHandle,
+        _client: &Client,
+        manager: New<ZwlrVirtualPointerManagerV1>,
+        _manager_state: &VirtualInputGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(manager, ());
+    }
+
+    fn can_view(client: Client, global_data: &VirtualInputGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrVirtualPointerManagerV1, (), D> for VirtualPointerManagerState
+where
+    D: Dispatch<ZwlrVirtualPointerManagerV1, ()>,
+    D: Dispatch<ZwlrVirtualPointerV1, VirtualPointerData>,
+    D: VirtualInputHandler,
+    D: 'static,
+{
+    fn request(
+        _state: &mut D,
+        _client: &Client,
+        _manager: &ZwlrVirtualPointerManagerV1,
+        request: zwlr_virtual_pointer_manager_v1::Request,
+        _data: &(),
+        _display: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            // there is only one seat, and absolute motion is mapped like that of real devices,
+            // whatever output was asked for
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointer { id, .. }
+            | zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointerWithOutput {
+                id, ..
+            } => {
+                data_init.init(id, VirtualPointerData::new());
+            }
+            zwlr_virtual_pointer_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrVirtualPointerV1, VirtualPointerData, D> for VirtualPointerManagerState
+where
+    D: Dispatch<ZwlrVirtualPointerV1, VirtualPointerData>,
+    D: VirtualInputHandler,
+    D: 'static,
+{
+    fn request(
+        state: &mut D,
+        _client: &Client,
+        pointer: &ZwlrVirtualPointerV1,
+        request: zwlr_virtual_pointer_v1::Request,
+        data: &VirtualPointerData,
+        _display: &DisplayHandle,
+        _data_init: &mut DataInit<'_, D>,
+    ) {
+        let device = data.device.clone();
+        match request {
+            zwlr_virtual_pointer_v1::Request::Motion { time, dx, dy } => {
+                state.virtual_input(InputEvent::PointerMotion {
+                    event: VirtualMotionEvent {
+                        device,
+                        time,
+                        dx,
+                        dy,
+                    },
+                });
+            }
+            zwlr_virtual_pointer_v1::Request::MotionAbsolute {
+                time,
+                x,
+                y,
+                x_extent,
+                y_extent,
+            } => {
+                if x_extent == 0 || y_extent == 0 {
+                    return;
+                }
+                state.virtual_input(InputEvent::PointerMotionAbsolute {
+                    event: VirtualMotionAbsoluteEvent {
+                        device,
+                        time,
+                        x,
+                        y,
+                        x_extent,
+                        y_extent,
+                    },
+                });
+            }
+            zwlr_virtual_pointer_v1::Request::Button {
+                time,
+                button,
+                state: button_state,
+            } => {
+                let button_state = match button_state {
+                    WEnum::Value(wl_pointer::ButtonState::Pressed) => ButtonState::Pressed,
+                    WEnum::Value(wl_pointer::ButtonState::Released) => ButtonState::Released,
+                    _ => return,
+                };
+                {
+                    let mut pointer_state = data.state.lock().unwrap();
+                    pointer_state.time = time;
+                    let pressed = pointer_state.pressed.contains(&button);
+                    match button_state {
+                        ButtonState::Pressed if !pressed => pointer_state.pressed.push(button),
+                        ButtonState::Released if pressed => {
+                            pointer_state.pressed.retain(|b| *b != button)
+                        }
+                        // repeated presses and releases of buttons that are not pressed
+                        _ => return,
+                    }
+                }
+                state.virtual_input(InputEvent::PointerButton {
+                    event: VirtualButtonEvent {
+                        device,
+                        time,
+                        button,
+                        state: button_state,
+                    },
+                });
+            }
+            zwlr_virtual_pointer_v1::Request::Axis {
+                time,
+                axis: wl_axis,
+                value,
+            } => {
+                if let Some(axis) = axis(pointer, wl_axis) {
+                    data.with_axis(Some(time), |event| event.set(axis, value, None));
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisDiscrete {
+                time,
+                axis: wl_axis,
+                value,
+                discrete,
+            } => {
+                if let Some(axis) = axis(pointer, wl_axis) {
+                    data.with_axis(Some(time), |event| {
+                        event.set(axis, value, Some(discrete as f64 * 120.0))
+                    });
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisStop {
+                time,
+                axis: wl_axis,
+            } => {
+                if let Some(axis) = axis(pointer, wl_axis) {
+                    data.with_axis(Some(time), |event| event.set(axis, 0.0, None));
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::AxisSource { axis_source } => {
+                let source = match axis_source {
+                    WEnum::Value(wl_pointer::AxisSource::Wheel) => AxisSource::Wheel,
+                    WEnum::Value(wl_pointer::AxisSource::Finger) => AxisSource::Finger,
+                    WEnum::Value(wl_pointer::AxisSource::Continuous) => AxisSource::Continuous,
+                    WEnum::Value(wl_pointer::AxisSource::WheelTilt) => AxisSource::WheelTilt,
+                    _ => {
+                        pointer.post_error(
+                            zwlr_virtual_pointer_v1::Error::InvalidAxisSource,
+                            "invalid axis source",
+                        );
+                        return;
+                    }
+                };
+                data.with_axis(None, |event| event.source = Some(source));
+            }
+            // motion and buttons are not held back until the frame, like those of libinput
+            zwlr_virtual_pointer_v1::Request::Frame => {
+                let axis = data.state.lock().unwrap().axis.take();
+                if let Some(event) = axis {
+                    state.virtual_input(InputEvent::PointerAxis { event });
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(
+        state: &mut D,
+        _client: ClientId,
+        _pointer: &ZwlrVirtualPointerV1,
+        data: &VirtualPointerData,
+    ) {
+        let (pressed, time) = {
+            let mut pointer_state = data.state.lock().unwrap();
+            (
+                std::mem::take(&mut pointer_state.pressed),
+                pointer_state.time,
+            )
+        };
+        for button in pressed {
+            state.virtual_input(InputEvent::PointerButton {
+                event: VirtualButtonEvent {
+                    device: data.device.clone(),
+                    time,
+                    button,
+                    state: ButtonState::Released,
+                },
+            });
+        }
+    }
+}
+
+#[allow(missing_docs)]
+#[macro_export]
+macro_rules! delegate_virtual_pointer {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1: $crate::protocols::virtual_input::VirtualInputGlobalData
+        ] => $crate::protocols::virtual_input::pointer::VirtualPointerManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1: ()
+        ] => $crate::protocols::virtual_input::pointer::VirtualPointerManagerState);
+
+        smithay::reexports::wayland_server::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1: $crate::protocols::virtual_input::pointer::VirtualPointerData
+        ] => $crate::protocols::virtual_input::pointer::VirtualPointerManagerState);
+    };
+}
@@ -1,37 +1,84 @@
-use std::{ffi::OsString, sync::Arc, time::Instant};
+use std::{
+    ffi::OsString,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 use once_cell::sync::Lazy;
 use smithay::{
     desktop::{
-        layer_map_for_output, {PopupManager, Window},
+        layer_map_for_output,
+        space::SpaceElement,
+        LayerSurface, {PopupManager, Window},
     },
-    input::{keyboard::XkbConfig, Seat, SeatState},
+    input::{keyboard::XkbConfig, pointer::CursorImageStatus, Seat, SeatState},
+    output::Output,
     reexports::{
-        calloop::{generic::Generic, Interest, LoopHandle, LoopSignal, Mode, PostAction},
+        calloop::{
+            generic::Generic,
+            timer::{TimeoutAction, Timer},
+            Interest, LoopHandle, LoopSignal, Mode, PostAction,
+        },
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason},
+            protocol::wl_surface::WlSurface,
             Display, DisplayHandle,
         },
     },
-    utils::{Logical, Point, Rectangle},
+    utils::{Clock, Logical, Monotonic, Point, Rectangle},
     wayland::{
         compositor::{CompositorClientState, CompositorState},
+        fractional_scale::FractionalScaleManagerState,
+        idle_inhibit::IdleInhibitManagerState,
+        idle_notify::IdleNotifierState,
+        input_method::InputMethodManagerState,
         output::OutputManagerState,
-        selection::{data_device::DataDeviceState, primary_selection::PrimarySelectionState},
+        pointer_constraints::PointerConstraintsState,
+        presentation::PresentationState,
+        relative_pointer::RelativePointerManagerState,
+        selection::{
+            data_device::DataDeviceState, primary_selection::PrimarySelectionState,
+            wlr_data_control::DataControlState,
+        },
+        session_lock::SessionLockManagerState,
         shell::{
-            wlr_layer::{Layer as WlrLayer, WlrLayerShellState},
+            wlr_layer::{KeyboardInteractivity, Layer as WlrLayer, WlrLayerShellState},
             xdg::{decoration::XdgDecorationState, XdgShellState},
         },
         shm::ShmState,
         socket::ListeningSocketSource,
+        text_input::TextInputManagerState,
+        viewporter::ViewporterState,
+        xdg_activation::XdgActivationState,
     },
+    xwayland::X11Wm,
 };
-use tracing::warn;
+use tracing::{info, warn};
 
-use crate::utils::{focus::FocusTarget, workspace::Workspaces};
+use crate::handlers::{
+    clipboard::PersistedClipboard,
+    idle::{Idle, IDLE_CHECK_INTERVAL},
+    session_lock::SessionLock,
+};
+use crate::utils::{
+    focus::FocusTarget,
+    render::{background::BackgroundImages, cursor::CursorTheme},
+    workspace::Workspaces,
+};
 use crate::{
     config::{load_config, Config},
     debug::MagmaDebug,
+    protocols::{
+        foreign_toplevel::ForeignToplevelManagerState,
+        foreign_toplevel_list::ForeignToplevelListState,
+        tearing_control::TearingControlManagerState,
+        virtual_input::{
+            keyboard::VirtualKeyboardManagerState, pointer::VirtualPointerManagerState,
+        },
+    },
 };
 
 pub struct CalloopData<BackendData: Backend + 'static> {
@@ -39,8 +86,17 @@ pub struct CalloopData<BackendData: Backend + 'static> {
     pub display_handle: DisplayHandle,
 }
 
-pub trait Backend {
+pub trait Backend: Sized + 'static {
     fn seat_name(&self) -> String;
+    /// Turn `output` off or back on (DPMS), if the backend can.
+    fn set_output_power(_state: &mut MagmaState<Self>, _output: &Output, _on: bool) {}
+    fn is_output_powered(&self, _output: &Output) -> bool {
+        true
+    }
+    /// Switch to another virtual terminal, if the backend runs in a session.
+    fn switch_vt(_state: &mut MagmaState<Self>, vt: i32) {
+        info!(vt, "Not switching vt without a session");
+    }
 }
 
 pub static CONFIG: Lazy<Config> = Lazy::new(load_config);
@@ -49,6 +105,7 @@ pub struct MagmaState<BackendData: Backend + 'static> {
     pub dh: DisplayHandle,
     pub backend_data: BackendData,
     pub start_time: Instant,
+    pub clock: Clock<Monotonic>,
     pub loop_handle: LoopHandle<'static, CalloopData<BackendData>>,
     pub loop_signal: LoopSignal,
 
@@ -60,8 +117,25 @@ pub struct MagmaState<BackendData: Backend + 'static> {
     pub output_manager_state: OutputManagerState,
     pub data_device_state: DataDeviceState,
     pub primary_selection_state: PrimarySelectionState,
+    pub data_control_state: DataControlState,
     pub seat_state: SeatState<MagmaState<BackendData>>,
     pub layer_shell_state: WlrLayerShellState,
+    pub session_lock_state: SessionLockManagerState,
+    pub idle_notifier_state: IdleNotifierState<Self>,
+    pub idle_inhibit_state: IdleInhibitManagerState,
+    pub foreign_toplevel_manager_state: ForeignToplevelManagerState,
+    pub foreign_toplevel_list_state: ForeignToplevelListState,
+    pub xdg_activation_state: XdgActivationState,
+    pub fractional_scale_manager_state: FractionalScaleManagerState,
+    pub viewporter_state: ViewporterState,
+    pub presentation_state: PresentationState,
+    pub tearing_control_state: TearingControlManagerState,
+    pub text_input_state: TextInputManagerState,
+    pub input_method_state: InputMethodManagerState,
+    pub virtual_keyboard_state: VirtualKeyboardManagerState,
+    pub virtual_pointer_state: VirtualPointerManagerState,
+    pub relative_pointer_state: RelativePointerManagerState,
+    pub pointer_constraints_state: PointerConstraintsState,
     pub popup_manager: PopupManager,
 
     pub seat: Seat<Self>,
@@ -70,6 +144,25 @@ pub struct MagmaState<BackendData: Backend + 'static> {
 
     pub workspaces: Workspaces,
     pub pointer_location: Point<f64, Logical>,
+    // where the last absolute motion put the pointer, before constraints
+    pub last_absolute_location: Option<Point<f64, Logical>>,
+    pub cursor_status: CursorImageStatus,
+    pub cursor_theme: CursorTheme,
+    // icon of the ongoing drag and drop operation, drawn under the cursor
+    pub dnd_icon: Option<DndIcon>,
+    pub persisted_clipboard: PersistedClipboard,
+    pub background_images: BackgroundImages,
+    // keymap of the virtual keyboard the seat keyboard was switched to
+    pub virtual_keymap: Option<Arc<str>>,
+
+    pub xwm: Option<X11Wm>,
+    pub xdisplay: Option<u32>,
+    // unmanaged X11 windows like menus, stacked above everything else
+    pub override_redirect: Vec<Window>,
+
+    // set while the session is locked
+    pub session_lock: Option<SessionLock>,
+    pub idle: Idle,
 
     #[cfg(feature = "debug")]
     pub debug: MagmaDebug,
@@ -83,6 +176,7 @@ impl<BackendData: Backend + 'static> MagmaState<BackendData> {
         backend_data: BackendData,
     ) -> Self {
         let start_time = Instant::now();
+        let clock = Clock::new();
 
         let dh = display.handle();
 
@@ -94,9 +188,27 @@ impl<BackendData: Backend + 'static> MagmaState<BackendData> {
         let mut seat_state = SeatState::new();
         let data_device_state = DataDeviceState::new::<Self>(&dh);
         let primary_selection_state = PrimarySelectionState::new::<Self>(&dh);
+        let data_control_state =
+            DataControlState::new::<Self, _>(&dh, Some(&primary_selection_state), |_| true);
         let seat_name = backend_data.seat_name();
         let mut seat = seat_state.new_wl_seat(&dh, seat_name.clone());
         let layer_shell_state = WlrLayerShellState::new::<Self>(&dh);
+        let session_lock_state = SessionLockManagerState::new::<Self, _>(&dh, |_| true);
+        let idle_notifier_state = IdleNotifierState::new(&dh, loop_handle.clone());
+        let idle_inhibit_state = IdleInhibitManagerState::new::<Self>(&dh);
+        let foreign_toplevel_manager_state = ForeignToplevelManagerState::new::<Self>(&dh);
+        let foreign_toplevel_list_state = ForeignToplevelListState::new::<Self>(&dh);
+        let xdg_activation_state = XdgActivationState::new::<Self>(&dh);
+        let fractional_scale_manager_state = FractionalScaleManagerState::new::<Self>(&dh);
+        let viewporter_state = ViewporterState::new::<Self>(&dh);
+        let presentation_state = PresentationState::new::<Self>(&dh, clock.id() as u32);
+        let tearing_control_state = TearingControlManagerState::new::<Self>(&dh);
+        let text_input_state = TextInputManagerState::new::<Self>(&dh);
+        let input_method_state = InputMethodManagerState::new::<Self, _>(&dh, |_| true);
+        let virtual_keyboard_state = VirtualKeyboardManagerState::new::<Self, _>(&dh, |_| true);
+        let virtual_pointer_state = VirtualPointerManagerState::new::<Self, _>(&dh, |_| true);
+        let relative_pointer_state = RelativePointerManagerState::new::<Self>(&dh);
+        let pointer_constraints_state = PointerConstraintsState::new::<Self>(&dh);
 
         let conf = CONFIG.xkb.clone();
         if let Err(err) = seat.add_keyboard((&conf).into(), 200, 25) {
@@ -146,11 +258,19 @@ impl<BackendData: Backend + 'static> MagmaState<BackendData> {
             )
             .expect("Failed to init wayland server source");
 
+        loop_handle
+            .insert_source(Timer::from_duration(IDLE_CHECK_INTERVAL), |_, _, data| {
+                data.state.check_idle();
+                TimeoutAction::ToDuration(IDLE_CHECK_INTERVAL)
+            })
+            .expect("Failed to init the idle timer");
+
         Self {
             loop_handle,
             dh,
             backend_data,
             start_time,
+            clock,
             seat_name,
             socket_name,
             compositor_state,
@@ -163,10 +283,39 @@ impl<BackendData: Backend + 'static> MagmaState<BackendData> {
             seat_state,
             data_device_state,
             primary_selection_state,
+            data_control_state,
             layer_shell_state,
+            session_lock_state,
+            idle_notifier_state,
+            idle_inhibit_state,
+            foreign_toplevel_manager_state,
+            foreign_toplevel_list_state,
+            xdg_activation_state,
+            fractional_scale_manager_state,
+            viewporter_state,
+            presentation_state,
+            tearing_control_state,
+            text_input_state,
+            input_method_state,
+            virtual_keyboard_state,
+            virtual_pointer_state,
+            relative_pointer_state,
+            pointer_constraints_state,
             seat,
             workspaces,
             pointer_location: Point::from((0.0, 0.0)),
+            last_absolute_location: None,
+            cursor_status: CursorImageStatus::default_named(),
+            cursor_theme: CursorTheme::load(),
+            dnd_icon: None,
+            persisted_clipboard: PersistedClipboard::default(),
+            background_images: BackgroundImages::default(),
+            virtual_keymap: None,
+            xwm: None,
+            xdisplay: None,
+            override_redirect: Vec::new(),
+            session_lock: None,
+            idle: Idle::default(),
             #[cfg(feature = "debug")]
             debug: MagmaDebug {
                 egui: smithay_egui::EguiState::new(Rectangle::from_loc_and_size(
@@ -186,17 +335,49 @@ impl<BackendData: Backend + 'static> MagmaState<BackendData> {
             .window_under(pos)
             .map(|(w, p)| (w.clone(), p))
     }
+    /// A top or overlay layer surface asking for exclusive keyboard focus, like a launcher.
+    pub fn exclusive_layer(&self) -> Option<LayerSurface> {
+        self.workspaces.current().outputs().find_map(|o| {
+            layer_map_for_output(o)
+                .layers()
+                .find(|l| {
+                    matches!(l.layer(), WlrLayer::Top | WlrLayer::Overlay)
+                        && l.cached_state().keyboard_interactivity
+                            == KeyboardInteractivity::Exclusive
+                })
+                .cloned()
+        })
+    }
+
     pub fn surface_under(&self) -> Option<(FocusTarget, Point<i32, Logical>)> {
-        let pos = self.pointer_location;
+        self.surface_under_at(self.pointer_location)
+    }
+
+    /// The surface at `pos` and its location.
+    pub fn surface_under_at(
+        &self,
+        pos: Point<f64, Logical>,
+    ) -> Option<(FocusTarget, Point<i32, Logical>)> {
         let output = self.workspaces.current().outputs().find(|o| {
             let geometry = self.workspaces.current().output_geometry(o).unwrap();
             geometry.contains(pos.to_i32_round())
         })?;
         let output_geo = self.workspaces.current().output_geometry(output).unwrap();
+        if let Some(lock) = &self.session_lock {
+            return lock
+                .surface(output)
+                .map(|surface| (surface.clone().into(), output_geo.loc));
+        }
         let layers = layer_map_for_output(output);
 
         let mut under = None;
-        if let Some(layer) = layers
+        if let Some((window, location)) = self.override_redirect.iter().rev().find_map(|w| {
+            let location = w.x11_surface()?.geometry().loc;
+            w.is_in_input_region(&(pos - location.to_f64()))
+                .then(|| (w.clone(), location))
+        }) {
+            under = Some((window.into(), location));
+        } else if let Some(layer) = layers
             .layer_under(WlrLayer::Overlay, pos)
             .or_else(|| layers.layer_under(WlrLayer::Top, pos))
         {
@@ -215,11 +396,21 @@ impl<BackendData: Backend + 'static> MagmaState<BackendData> {
     }
 }
 
+/// Icon surface of a drag and drop operation.
+pub struct DndIcon {
+    pub surface: WlSurface,
+    // sum of the offsets the icon attached its buffers with, relative to the pointer
+    pub offset: Point<i32, Logical>,
+}
+
 #[derive(Default)]
 pub struct ClientState {
     pub compositor_state: CompositorClientState,
+    pub disconnected: AtomicBool,
 }
 impl ClientData for ClientState {
     fn initialized(&self, _client_id: ClientId) {}
-    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {}
+    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) {
+        self.disconnected.store(true, Ordering::Relaxed);
+    }
 }
@@ -9,7 +9,7 @@ pub use smithay::{
     },
     reexports::wayland_server::{backend::ObjectId, protocol::wl_surface::WlSurface, Resource},
     utils::{IsAlive, Serial},
-    wayland::seat::WaylandFocus,
+    wayland::{seat::WaylandFocus, session_lock::LockSurface},
 };
 
 use crate::state::{Backend, MagmaState};
@@ -19,6 +19,7 @@ pub enum FocusTarget {
     Window(Window),
     LayerSurface(LayerSurface),
     Popup(PopupKind),
+    LockSurface(LockSurface),
 }
 
 impl IsAlive for FocusTarget {
@@ -27,16 +28,11 @@ impl IsAlive for FocusTarget {
             FocusTarget::Window(w) => w.alive(),
             FocusTarget::LayerSurface(l) => l.alive(),
             FocusTarget::Popup(p) => p.alive(),
+            FocusTarget::LockSurface(l) => l.wl_surface().alive(),
         }
     }
 }
 
-impl From<FocusTarget> for WlSurface {
-    fn from(target: FocusTarget) -> Self {
-        target.wl_surface().unwrap()
-    }
-}
-
 impl<BackendData: Backend> PointerTarget<MagmaState<BackendData>> for FocusTarget {
     fn enter(
         &self,
@@ -45,11 +41,15 @@ impl<BackendData: Backend> PointerTarget<MagmaState<BackendData>> for FocusTarge
         event: &MotionEvent,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                PointerTarget::enter(&w.wl_surface().unwrap(), seat, data, event)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    PointerTarget::enter(w.wl_surface(), seat, data, event)
+                }
+                WindowSurface::X11(s) => PointerTarget::enter(s, seat, data, event),
+            },
             FocusTarget::LayerSurface(l) => PointerTarget::enter(l.wl_surface(), seat, data, event),
             FocusTarget::Popup(p) => PointerTarget::enter(p.wl_surface(), seat, data, event),
+            FocusTarget::LockSurface(l) => PointerTarget::enter(l.wl_surface(), seat, data, event),
         }
     }
     fn motion(
@@ -59,13 +59,17 @@ impl<BackendData: Backend> PointerTarget<MagmaState<BackendData>> for FocusTarge
         event: &MotionEvent,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                PointerTarget::motion(&w.wl_surface().unwrap(), seat, data, event)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    PointerTarget::motion(w.wl_surface(), seat, data, event)
+                }
+                WindowSurface::X11(s) => PointerTarget::motion(s, seat, data, event),
+            },
             FocusTarget::LayerSurface(l) => {
                 PointerTarget::motion(l.wl_surface(), seat, data, event)
             }
             FocusTarget::Popup(p) => PointerTarget::motion(p.wl_surface(), seat, data, event),
+            FocusTarget::LockSurface(l) => PointerTarget::motion(l.wl_surface(), seat, data, event),
         }
     }
     fn relative_motion(
@@ -75,15 +79,21 @@ impl<BackendData: Backend> PointerTarget<MagmaState<BackendData>> for FocusTarge
         event: &RelativeMotionEvent,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                PointerTarget::relative_motion(&w.wl_surface().unwrap(), seat, data, event)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    PointerTarget::relative_motion(w.wl_surface(), seat, data, event)
+                }
+                WindowSurface::X11(s) => PointerTarget::relative_motion(s, seat, data, event),
+            },
             FocusTarget::LayerSurface(l) => {
                 PointerTarget::relative_motion(l.wl_surface(), seat, data, event)
             }
             FocusTarget::Popup(p) => {
                 PointerTarget::relative_motion(p.wl_surface(), seat, data, event)
             }
+            FocusTarget::LockSurface(l) => {
+                PointerTarget::relative_motion(l.wl_surface(), seat, data, event)
+            }
         }
     }
     fn button(
@@ -93,13 +103,17 @@ impl<BackendData: Backend> PointerTarget<MagmaState<BackendData>> for FocusTarge
         event: &ButtonEvent,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                PointerTarget::button(&w.wl_surface().unwrap(), seat, data, event)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    PointerTarget::button(w.wl_surface(), seat, data, event)
+                }
+                WindowSurface::X11(s) => PointerTarget::button(s, seat, data, event),
+            },
             FocusTarget::LayerSurface(l) => {
                 PointerTarget::button(l.wl_surface(), seat, data, event)
             }
             FocusTarget::Popup(p) => PointerTarget::button(p.wl_surface(), seat, data, event),
+            FocusTarget::LockSurface(l) => PointerTarget::button(l.wl_surface(), seat, data, event),
         }
     }
     fn axis(
@@ -109,11 +123,13 @@ impl<BackendData: Backend> PointerTarget<MagmaState<BackendData>> for FocusTarge
         frame: AxisFrame,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                PointerTarget::axis(&w.wl_surface().unwrap(), seat, data, frame)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => PointerTarget::axis(w.wl_surface(), seat, data, frame),
+                WindowSurface::X11(s) => PointerTarget::axis(s, seat, data, frame),
+            },
             FocusTarget::LayerSurface(l) => PointerTarget::axis(l.wl_surface(), seat, data, frame),
             FocusTarget::Popup(p) => PointerTarget::axis(p.wl_surface(), seat, data, frame),
+            FocusTarget::LockSurface(l) => PointerTarget::axis(l.wl_surface(), seat, data, frame),
         }
     }
     fn leave(
@@ -124,13 +140,19 @@ impl<BackendData: Backend> PointerTarget<MagmaState<BackendData>> for FocusTarge
         time: u32,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                PointerTarget::leave(&w.wl_surface().unwrap(), seat, data, serial, time)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    PointerTarget::leave(w.wl_surface(), seat, data, serial, time)
+                }
+                WindowSurface::X11(s) => PointerTarget::leave(s, seat, data, serial, time),
+            },
             FocusTarget::LayerSurface(l) => {
                 PointerTarget::leave(l.wl_surface(), seat, data, serial, time)
             }
             FocusTarget::Popup(p) => PointerTarget::leave(p.wl_surface(), seat, data, serial, time),
+            FocusTarget::LockSurface(l) => {
+                PointerTarget::leave(l.wl_surface(), seat, data, serial, time)
+            }
         }
     }
 
@@ -220,16 +242,21 @@ impl<BackendData: Backend> KeyboardTarget<MagmaState<BackendData>> for FocusTarg
         serial: Serial,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                let WindowSurface::Wayland(w) = w.underlying_surface();
-                KeyboardTarget::enter(w.wl_surface(), seat, data, keys, serial)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    KeyboardTarget::enter(w.wl_surface(), seat, data, keys, serial)
+                }
+                WindowSurface::X11(s) => KeyboardTarget::enter(s, seat, data, keys, serial),
+            },
             FocusTarget::LayerSurface(l) => {
                 KeyboardTarget::enter(l.wl_surface(), seat, data, keys, serial)
             }
             FocusTarget::Popup(p) => {
                 KeyboardTarget::enter(p.wl_surface(), seat, data, keys, serial)
             }
+            FocusTarget::LockSurface(l) => {
+                KeyboardTarget::enter(l.wl_surface(), seat, data, keys, serial)
+            }
         }
     }
     fn leave(
@@ -239,14 +266,19 @@ impl<BackendData: Backend> KeyboardTarget<MagmaState<BackendData>> for FocusTarg
         serial: Serial,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                let WindowSurface::Wayland(w) = w.underlying_surface();
-                KeyboardTarget::leave(w.wl_surface(), seat, data, serial)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    KeyboardTarget::leave(w.wl_surface(), seat, data, serial)
+                }
+                WindowSurface::X11(s) => KeyboardTarget::leave(s, seat, data, serial),
+            },
             FocusTarget::LayerSurface(l) => {
                 KeyboardTarget::leave(l.wl_surface(), seat, data, serial)
             }
             FocusTarget::Popup(p) => KeyboardTarget::leave(p.wl_surface(), seat, data, serial),
+            FocusTarget::LockSurface(l) => {
+                KeyboardTarget::leave(l.wl_surface(), seat, data, serial)
+            }
         }
     }
     fn key(
@@ -259,16 +291,23 @@ impl<BackendData: Backend> KeyboardTarget<MagmaState<BackendData>> for FocusTarg
         time: u32,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                let WindowSurface::Wayland(w) = w.underlying_surface();
-                KeyboardTarget::key(w.wl_surface(), seat, data, key, state, serial, time)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    KeyboardTarget::key(w.wl_surface(), seat, data, key, state, serial, time)
+                }
+                WindowSurface::X11(s) => {
+                    KeyboardTarget::key(s, seat, data, key, state, serial, time)
+                }
+            },
             FocusTarget::LayerSurface(l) => {
                 KeyboardTarget::key(l.wl_surface(), seat, data, key, state, serial, time)
             }
             FocusTarget::Popup(p) => {
                 KeyboardTarget::key(p.wl_surface(), seat, data, key, state, serial, time)
             }
+            FocusTarget::LockSurface(l) => {
+                KeyboardTarget::key(l.wl_surface(), seat, data, key, state, serial, time)
+            }
         }
     }
     fn modifiers(
@@ -279,16 +318,23 @@ impl<BackendData: Backend> KeyboardTarget<MagmaState<BackendData>> for FocusTarg
         serial: Serial,
     ) {
         match self {
-            FocusTarget::Window(w) => {
-                let WindowSurface::Wayland(w) = w.underlying_surface();
-                KeyboardTarget::modifiers(w.wl_surface(), seat, data, modifiers, serial)
-            }
+            FocusTarget::Window(w) => match w.underlying_surface() {
+                WindowSurface::Wayland(w) => {
+                    KeyboardTarget::modifiers(w.wl_surface(), seat, data, modifiers, serial)
+                }
+                WindowSurface::X11(s) => {
+                    KeyboardTarget::modifiers(s, seat, data, modifiers, serial)
+                }
+            },
             FocusTarget::LayerSurface(l) => {
                 KeyboardTarget::modifiers(l.wl_surface(), seat, data, modifiers, serial)
             }
             FocusTarget::Popup(p) => {
                 KeyboardTarget::modifiers(p.wl_surface(), seat, data, modifiers, serial)
             }
+            FocusTarget::LockSurface(l) => {
+                KeyboardTarget::modifiers(l.wl_surface(), seat, data, modifiers, serial)
+            }
         }
     }
 }
@@ -299,6 +345,7 @@ impl WaylandFocus for FocusTarget {
             FocusTarget::Window(w) => w.wl_surface(),
             FocusTarget::LayerSurface(l) => Some(l.wl_surface().clone()),
             FocusTarget::Popup(p) => Some(p.wl_surface().clone()),
+            FocusTarget::LockSurface(l) => Some(l.wl_surface().clone()),
         }
     }
     fn same_client_as(&self, object_id: &ObjectId) -> bool {
@@ -306,6 +353,7 @@ impl WaylandFocus for FocusTarget {
             FocusTarget::Window(w) => w.same_client_as(object_id),
             FocusTarget::LayerSurface(l) => l.wl_surface().id().same_client_as(object_id),
             FocusTarget::Popup(p) => p.wl_surface().id().same_client_as(object_id),
+            FocusTarget::LockSurface(l) => l.wl_surface().id().same_client_as(object_id),
         }
     }
 }
@@ -327,3 +375,9 @@ impl From<PopupKind> for FocusTarget {
         FocusTarget::Popup(p)
     }
 }
+
+impl From<LockSurface> for FocusTarget {
+    fn from(l: LockSurface) -> Self {
+        FocusTarget::LockSurface(l)
+    }
+}
@@ -1,7 +1,9 @@
 pub mod binarytree;
 pub mod focus;
 pub mod log;
+pub mod modeline;
 pub mod process;
 pub mod render;
 pub mod tiling;
+pub mod window;
 pub mod workspace;
@@ -0,0 +1,168 @@
+use smithay::reexports::drm::{
+    control::{Mode as DrmMode, ModeFlags, ModeTypeFlags},
+    ffi::drm_mode_modeinfo,
+};
+
+// Constants from the VESA Coordinated Video Timings standard, as used by libxcvt
+const CVT_H_GRANULARITY: u64 = 8;
+const CVT_MIN_V_PORCH: u64 = 3;
+const CVT_MIN_V_BPORCH: u64 = 6;
+const CVT_CLOCK_STEP: u64 = 250;
+const CVT_MIN_VSYNC_BP: f64 = 550.0;
+const CVT_HSYNC_PERCENTAGE: u64 = 8;
+const CVT_C_PRIME: f64 = 30.0;
+const CVT_M_PRIME: f64 = 300.0;
+
+/// Generate a CVT mode (normal blanking) for the given size and refresh rate in Hz.
+///
+/// Returns `None` if the size or refresh rate can't be described by a DRM mode.
+pub fn cvt(width: u32, height: u32, refresh: f64) -> Option<DrmMode> {
+    let refresh = if refresh > 0.0 { refresh } else { 60.0 };
+    let width = u64::from(width);
+    let hdisplay = width - width % CVT_H_GRANULARITY;
+    let vdisplay = u64::from(height);
+    if hdisplay == 0 || vdisplay == 0 {
+        return None;
+    }
+
+    let vsync = match (hdisplay * 3 / 4, hdisplay * 9 / 16, hdisplay * 10 / 16) {
+        (h, _, _) if h == vdisplay => 4,
+        (_, h, _) if h == vdisplay => 5,
+        (_, _, h) if h == vdisplay => 6,
+        _ if hdisplay * 4 / 5 == vdisplay || hdisplay * 9 / 15 == vdisplay => 7,
+        _ => 10,
+    };
+
+    // horizontal period estimate in us
+    let hperiod = (1_000_000.0 / refresh - CVT_MIN_VSYNC_BP) / (vdisplay + CVT_MIN_V_PORCH) as f64;
+    // refresh rates too high to leave time for the vertical blanking
+    if hperiod <= 0.0 {
+        return None;
+    }
+    let vsync_bp = ((CVT_MIN_VSYNC_BP / hperiod) as u64 + 1).max(vsync + CVT_MIN_V_BPORCH);
+    let vtotal = vdisplay + vsync_bp + CVT_MIN_V_PORCH;
+
+    let hblank_percentage = (CVT_C_PRIME - CVT_M_PRIME * hperiod / 1000.0).max(20.0);
+    let mut hblank = (hdisplay as f64 * hblank_percentage / (100.0 - hblank_percentage)) as u64;
+    hblank -= hblank % (2 * CVT_H_GRANULARITY);
+    let htotal = hdisplay + hblank;
+
+    let hsync_end = hdisplay + hblank / 2;
+    let mut hsync = htotal * CVT_HSYNC_PERCENTAGE / 100;
+    hsync -= hsync % CVT_H_GRANULARITY;
+    let hsync_start = hsync_end - hsync;
+
+    let vsync_start = vdisplay + CVT_MIN_V_PORCH;
+    let vsync_end = vsync_start + vsync;
+
+    let mut clock = (htotal as f64 * 1000.0 / hperiod) as u64;
+    clock -= clock % CVT_CLOCK_STEP;
+
+    drm_mode(
+        clock,
+        [hdisplay, hsync_start, hsync_end, htotal],
+        [vdisplay, vsync_start, vsync_end, vtotal],
+        ModeFlags::NHSYNC | ModeFlags::PVSYNC,
+    )
+}
+
+/// Parse an X11 style modeline, e.g. the output of the `cvt` tool:
+///
+/// `Modeline "1920x1080_60.00" 173.00 1920 2048 2248 2576 1080 1083 1088 1120 -hsync +vsync`
+///
+/// The `Modeline` keyword and name are optional.
+pub fn parse_modeline(modeline: &str) -> Result<DrmMode, String> {
+    let mut args = modeline.split_whitespace().peekable();
+    if args
+        .peek()
+        .is_some_and(|arg| arg.eq_ignore_ascii_case("modeline"))
+    {
+        args.next();
+    }
+    if args.peek().is_some_and(|arg| arg.starts_with('"')) {
+        args.next();
+    }
+
+    let clock = args
+        .next()
+        .and_then(|clock| clock.parse::<f64>().ok())
+        .ok_or_else(|| format!("Missing or invalid pixel clock in modeline '{}'", modeline))?;
+    let mut timings = [0u64; 8];
+    for timing in timings.iter_mut() {
+        *timing = args
+            .next()
+            .and_then(|timing| timing.parse().ok())
+            .ok_or_else(|| format!("Missing or invalid timing in modeline '{}'", modeline))?;
+    }
+    let (hdisplay, hsync_start, hsync_end, htotal) =
+        (timings[0], timings[1], timings[2], timings[3]);
+    let (vdisplay, vsync_start, vsync_end, vtotal) =
+        (timings[4], timings[5], timings[6], timings[7]);
+    if !(hdisplay < hsync_start && hsync_start < hsync_end && hsync_end <= htotal)
+        || !(vdisplay < vsync_start && vsync_start < vsync_end && vsync_end <= vtotal)
+    {
+        return Err(format!("Inconsistent timings in modeline '{}'", modeline));
+    }
+
+    let mut flags = ModeFlags::empty();
+    for flag in args {
+        flags |= match flag.to_lowercase().as_str() {
+            "+hsync" => ModeFlags::PHSYNC,
+            "-hsync" => ModeFlags::NHSYNC,
+            "+vsync" => ModeFlags::PVSYNC,
+            "-vsync" => ModeFlags::NVSYNC,
+            "interlace" => ModeFlags::INTERLACE,
+            "doublescan" => ModeFlags::DBLSCAN,
+            _ => {
+                return Err(format!(
+                    "Unknown flag '{}' in modeline '{}'",
+                    flag, modeline
+                ))
+            }
+        };
+    }
+
+    drm_mode(
+        (clock * 1000.0) as u64,
+        [hdisplay, hsync_start, hsync_end, htotal],
+        [vdisplay, vsync_start, vsync_end, vtotal],
+        flags,
+    )
+    .ok_or_else(|| format!("Timings out of range in modeline '{}'", modeline))
+}
+
+// `None` if a timing doesn't fit the fields of a DRM mode
+fn drm_mode(clock: u64, h: [u64; 4], v: [u64; 4], flags: ModeFlags) -> Option<DrmMode> {
+    if clock == 0 || h[3] == 0 || v[3] == 0 {
+        return None;
+    }
+    let vrefresh = (clock as f64 * 1000.0 / (h[3] as f64 * v[3] as f64)).round();
+    let [hdisplay, hsync_start, hsync_end, htotal] = h.map(u16::try_from);
+    let [vdisplay, vsync_start, vsync_end, vtotal] = v.map(u16::try_from);
+
+    let mut name = [0; 32];
+    for (dst, src) in name
+        .iter_mut()
+        .zip(format!("{}x{}", h[0], v[0]).bytes().take(31))
+    {
+        *dst = src as _;
+    }
+
+    Some(DrmMode::from(drm_mode_modeinfo {
+        clock: u32::try_from(clock).ok()?,
+        hdisplay: hdisplay.ok()?,
+        hsync_start: hsync_start.ok()?,
+        hsync_end: hsync_end.ok()?,
+        htotal: htotal.ok()?,
+        hskew: 0,
+        vdisplay: vdisplay.ok()?,
+        vsync_start: vsync_start.ok()?,
+        vsync_end: vsync_end.ok()?,
+        vtotal: vtotal.ok()?,
+        vscan: 0,
+        vrefresh: vrefresh as u32,
+        flags: flags.bits(),
+        type_: ModeTypeFlags::USERDEF.bits(),
+        name,
+    }))
+}
@@ -1,6 +1,8 @@
 use std::process::{Child, Command};
 use tracing::{debug, info};
 
+use crate::state::CONFIG;
+
 pub fn spawn(command: &str) -> Option<Child> {
     debug!("Spawning '{command}'");
     Command::new("/bin/sh")
@@ -10,3 +12,9 @@ pub fn spawn(command: &str) -> Option<Child> {
         .map_err(|e| info!("Failed to spawn '{command}': {e}"))
         .ok()
 }
+
+pub fn spawn_autostart() {
+    for command in &CONFIG.autostart {
+        spawn(command);
+    }
+}
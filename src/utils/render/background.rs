@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use image::{
+    imageops::{self, FilterType},
+    Rgba, RgbaImage,
+};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{element::texture::TextureBuffer, gles::GlesTexture, glow::GlowRenderer},
+    },
+    output::Output,
+    utils::{Physical, Rectangle, Size, Transform},
+};
+use tracing::warn;
+
+use crate::{
+    config::{Background, BackgroundMode},
+    state::CONFIG,
+};
+
+const DEFAULT_COLOR: [f32; 3] = [0.1, 0.1, 0.1];
+
+/// Decoded background images by path, so outputs sharing a wallpaper only decode it once.
+#[derive(Default)]
+pub struct BackgroundImages(HashMap<PathBuf, Option<RgbaImage>>);
+
+fn color(output: &Output) -> [f32; 3] {
+    match CONFIG.background(&output.name()) {
+        Some(Background::Color(color)) => *color,
+        _ => DEFAULT_COLOR,
+    }
+}
+
+/// Colour the output is cleared with before anything else is drawn.
+pub fn clear_color(output: &Output) -> [f32; 4] {
+    let [r, g, b] = color(output);
+    [r, g, b, 1.0]
+}
+
+/// Render the configured background image of an output into a texture of the size of its
+/// current mode after its transform, to be drawn below everything else.
+pub fn background_texture(
+    renderer: &mut GlowRenderer,
+    output: &Output,
+    images: &mut BackgroundImages,
+) -> Option<TextureBuffer<GlesTexture>> {
+    // images no longer configured for any output are not needed anymore
+    images.0.retain(|path, _| {
+        CONFIG
+            .background
+            .values()
+            .any(|background| matches!(background, Background::Image { path: p, .. } if p == path))
+    });
+
+    let Some(Background::Image { path, mode }) = CONFIG.background(&output.name()) else {
+        return None;
+    };
+    // elements are laid out in the transformed space of the output
+    let size = output
+        .current_transform()
+        .transform_size(output.current_mode()?.size);
+    if size.w <= 0 || size.h <= 0 {
+        return None;
+    }
+
+    let image = images
+        .0
+        .entry(path.clone())
+        .or_insert_with(|| match image::open(expand_home(path)) {
+            // it could not be scaled to the output, the clear colour is shown instead
+            Ok(image) if image.width() == 0 || image.height() == 0 => {
+                warn!("Background {} is empty", path.display());
+                None
+            }
+            Ok(image) => Some(image.into_rgba8()),
+            Err(err) => {
+                warn!("Failed to load background {}: {}", path.display(), err);
+                None
+            }
+        })
+        .as_ref()?;
+    let background = compose(image, size, *mode);
+
+    TextureBuffer::from_memory(
+        renderer,
+        background.as_raw(),
+        Fourcc::Abgr8888,
+        (size.w, size.h),
+        false,
+        1,
+        Transform::Normal,
+        Some(vec![Rectangle::from_loc_and_size((0, 0), (size.w, size.h))]),
+    )
+    .map_err(|err| warn!("Failed to upload background texture: {}", err))
+    .ok()
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => Path::new(&home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn compose(image: &RgbaImage, size: Size<i32, Physical>, mode: BackgroundMode) -> RgbaImage {
+    let (width, height) = (size.w as u32, size.h as u32);
+    let [r, g, b] = DEFAULT_COLOR.map(|c| (c * 255.0) as u8);
+    let mut canvas = RgbaImage::from_pixel(width, height, Rgba([r, g, b, 255]));
+
+    let centered = |w: u32, h: u32| {
+        (
+            (width as i64 - w as i64) / 2,
+            (height as i64 - h as i64) / 2,
+        )
+    };
+
+    match mode {
+        BackgroundMode::Fill | BackgroundMode::Fit => {
+            let scale_x = width as f64 / image.width() as f64;
+            let scale_y = height as f64 / image.height() as f64;
+            let scale = if mode == BackgroundMode::Fill {
+                scale_x.max(scale_y)
+            } else {
+                scale_x.min(scale_y)
+            };
+            let w = ((image.width() as f64 * scale).round() as u32).max(1);
+            let h = ((image.height() as f64 * scale).round() as u32).max(1);
+            let scaled = imageops::resize(image, w, h, FilterType::Triangle);
+            let (x, y) = centered(w, h);
+            imageops::overlay(&mut canvas, &scaled, x, y);
+        }
+        BackgroundMode::Center => {
+            let (x, y) = centered(image.width(), image.height());
+            imageops::overlay(&mut canvas, image, x, y);
+        }
+        BackgroundMode::Tile => {
+            for y in (0..height).step_by(image.height().max(1) as usize) {
+                for x in (0..width).step_by(image.width().max(1) as usize) {
+                    imageops::overlay(&mut canvas, image, x as i64, y as i64);
+                }
+            }
+        }
+    }
+    canvas
+}
@@ -2,7 +2,7 @@ use std::{borrow::BorrowMut, cell::RefCell, collections::HashMap};
 
 use smithay::{
     backend::renderer::{
-        element::{Element, Kind},
+        element::Kind,
         gles::{
             element::PixelShaderElement, GlesPixelProgram, GlesRenderer, Uniform, UniformName,
             UniformType,
@@ -22,7 +22,15 @@ pub struct BorderShader {
     default: GlesPixelProgram,
 }
 
-struct BorderShaderElements(RefCell<HashMap<Window, PixelShaderElement>>);
+struct BorderElement {
+    element: PixelShaderElement,
+    area: Rectangle<i32, Logical>,
+    // the uniforms depend on these
+    urgent: bool,
+    scale: f64,
+}
+
+struct BorderShaderElements(RefCell<HashMap<Window, BorderElement>>);
 
 impl BorderShader {
     pub fn init(renderer: &mut GlowRenderer) {
@@ -71,14 +79,16 @@ impl BorderShader {
     pub fn element(
         renderer: &mut GlowRenderer,
         window: &Window,
-        loc: Point<i32, Logical>,
+        area: Rectangle<i32, Logical>,
+        urgent: bool,
+        scale: f64,
     ) -> PixelShaderElement {
         let thickness: f32 = CONFIG.borders.thickness as f32;
         let thickness_loc = (thickness as i32, thickness as i32);
         let thickness_size = ((thickness * 2.0) as i32, (thickness * 2.0) as i32);
         let geo = Rectangle::from_loc_and_size(
-            loc - Point::from(thickness_loc),
-            window.geometry().size + Size::from(thickness_size),
+            area.loc - Point::from(thickness_loc),
+            area.size + Size::from(thickness_size),
         );
         let elements = &mut renderer
             .egl_context()
@@ -87,14 +97,31 @@ impl BorderShader {
             .expect("Border Shader not initialized")
             .0
             .borrow_mut();
-        if let Some(elem) = elements.get_mut(window) {
-            if elem.geometry(1.0.into()).to_logical(1) != geo {
-                elem.resize(geo, None);
+        if let Some(elem) = elements
+            .get_mut(window)
+            .filter(|elem| elem.urgent == urgent && elem.scale == scale)
+        {
+            if elem.area != geo {
+                elem.element.resize(geo, None);
+                elem.area = geo;
             }
-            elem.clone()
+            elem.element.clone()
         } else {
+            // the shaders work in physical pixels
+            let thickness = thickness * scale as f32;
             let angle = CONFIG.borders.gradient_angle * std::f32::consts::PI;
             let gradient_direction = [angle.cos(), angle.sin()];
+            let (start_color, end_color) = if urgent {
+                (CONFIG.borders.urgent_color, CONFIG.borders.urgent_color)
+            } else {
+                (
+                    CONFIG.borders.start_color,
+                    CONFIG
+                        .borders
+                        .end_color
+                        .unwrap_or(CONFIG.borders.start_color),
+                )
+            };
             let elem = if CONFIG.borders.radius > 0.0 {
                 PixelShaderElement::new(
                     Self::get(renderer).rounded.clone(),
@@ -102,17 +129,14 @@ impl BorderShader {
                     None,
                     1.0,
                     vec![
-                        Uniform::new("startColor", CONFIG.borders.start_color),
-                        Uniform::new(
-                            "endColor",
-                            CONFIG
-                                .borders
-                                .end_color
-                                .unwrap_or(CONFIG.borders.start_color),
-                        ),
+                        Uniform::new("startColor", start_color),
+                        Uniform::new("endColor", end_color),
                         Uniform::new("thickness", thickness),
                         Uniform::new("halfThickness", thickness * 0.5),
-                        Uniform::new("radius", CONFIG.borders.radius + thickness + 2.0),
+                        Uniform::new(
+                            "radius",
+                            CONFIG.borders.radius * scale as f32 + thickness + 2.0,
+                        ),
                         Uniform::new("gradientDirection", gradient_direction),
                     ],
                     Kind::Unspecified,
@@ -124,14 +148,8 @@ impl BorderShader {
                     None,
                     1.0,
                     vec![
-                        Uniform::new("startColor", CONFIG.borders.start_color),
-                        Uniform::new(
-                            "endColor",
-                            CONFIG
-                                .borders
-                                .end_color
-                                .unwrap_or(CONFIG.borders.start_color),
-                        ),
+                        Uniform::new("startColor", start_color),
+                        Uniform::new("endColor", end_color),
                         Uniform::new("thickness", thickness),
                         Uniform::new("halfThickness", thickness * 0.5),
                         Uniform::new("gradientDirection", gradient_direction),
@@ -139,7 +157,15 @@ impl BorderShader {
                     Kind::Unspecified,
                 )
             };
-            elements.insert(window.clone(), elem.clone());
+            elements.insert(
+                window.clone(),
+                BorderElement {
+                    element: elem.clone(),
+                    area: geo,
+                    urgent,
+                    scale,
+                },
+            );
             elem
         }
     }
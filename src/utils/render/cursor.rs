@@ -0,0 +1,231 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    env,
+    sync::Mutex,
+    time::Duration,
+};
+
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                surface::render_elements_from_surface_tree,
+                texture::{TextureBuffer, TextureRenderElement},
+                Kind,
+            },
+            gles::GlesTexture,
+            ImportAll, ImportMem, Renderer,
+        },
+    },
+    input::pointer::{CursorIcon, CursorImageAttributes, CursorImageStatus},
+    utils::{Logical, Point, Scale, Transform},
+    wayland::compositor::with_states,
+};
+use tracing::warn;
+use xcursor::{parser::parse_xcursor, CursorTheme as XCursorTheme};
+
+use crate::state::CONFIG;
+
+use super::{AsGlowRenderer, CustomRenderElements};
+
+static FALLBACK_CURSOR_DATA: &[u8] = include_bytes!("../../../resources/cursor.rgba");
+
+/// A single image of a (possibly animated) cursor.
+struct Frame {
+    size: (i32, i32),
+    hotspot: (i32, i32),
+    scale: i32,
+    delay: u32,
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    fn fallback() -> Self {
+        Frame {
+            size: (64, 64),
+            hotspot: (0, 0),
+            scale: 2,
+            delay: 0,
+            pixels: FALLBACK_CURSOR_DATA.to_vec(),
+        }
+    }
+}
+
+/// XCursor theme used for cursors not provided by clients.
+///
+/// The theme and size are taken from the config, then from
+/// `XCURSOR_THEME`/`XCURSOR_SIZE`.
+pub struct CursorTheme {
+    theme: XCursorTheme,
+    size: u32,
+    icons: HashMap<CursorIcon, Vec<Frame>>,
+}
+
+impl CursorTheme {
+    pub fn load() -> Self {
+        let name = CONFIG
+            .cursor
+            .theme
+            .clone()
+            .or_else(|| env::var("XCURSOR_THEME").ok())
+            .unwrap_or_else(|| "default".to_string());
+        let size = CONFIG
+            .cursor
+            .size
+            .or_else(|| env::var("XCURSOR_SIZE").ok()?.parse().ok())
+            .unwrap_or(24);
+
+        // let clients use the same theme
+        env::set_var("XCURSOR_THEME", &name);
+        env::set_var("XCURSOR_SIZE", size.to_string());
+
+        CursorTheme {
+            theme: XCursorTheme::load(&name),
+            size,
+            icons: HashMap::new(),
+        }
+    }
+
+    fn load_icon(&self, icon: CursorIcon) -> Option<Vec<Frame>> {
+        let path = std::iter::once(icon.name())
+            .chain(icon.alt_names().iter().copied())
+            .find_map(|name| self.theme.load_icon(name))?;
+        let images = parse_xcursor(&std::fs::read(path).ok()?)?;
+
+        // only use the images closest to the requested size
+        let nearest = images
+            .iter()
+            .min_by_key(|image| (image.size as i32 - self.size as i32).abs())?
+            .size;
+        Some(
+            images
+                .into_iter()
+                .filter(|image| image.size == nearest)
+                .map(|image| Frame {
+                    size: (image.width as i32, image.height as i32),
+                    hotspot: (image.xhot as i32, image.yhot as i32),
+                    scale: 1,
+                    delay: image.delay,
+                    pixels: image.pixels_rgba,
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the index and image of the frame of `icon` to show at `time`.
+    fn frame(&mut self, icon: CursorIcon, time: Duration) -> (usize, &Frame) {
+        if !self.icons.contains_key(&icon) {
+            let frames = self
+                .load_icon(icon)
+                .or_else(|| self.load_icon(CursorIcon::Default))
+                .unwrap_or_else(|| {
+                    warn!("Cursor {} not found in theme, using fallback", icon.name());
+                    vec![Frame::fallback()]
+                });
+            self.icons.insert(icon, frames);
+        }
+        let frames = &self.icons[&icon];
+
+        let duration: u32 = frames.iter().map(|frame| frame.delay).sum();
+        if duration > 0 {
+            let mut millis = (time.as_millis() % duration as u128) as u32;
+            for (index, frame) in frames.iter().enumerate() {
+                if millis < frame.delay {
+                    return (index, frame);
+                }
+                millis -= frame.delay;
+            }
+        }
+        (0, &frames[0])
+    }
+
+    /// Pixels, size and hotspot of the first frame of `icon`.
+    pub fn image(&mut self, icon: CursorIcon) -> (&[u8], (i32, i32), (i32, i32)) {
+        let (_, frame) = self.frame(icon, Duration::ZERO);
+        (&frame.pixels, frame.size, frame.hotspot)
+    }
+}
+
+/// Textures of theme cursors uploaded to a renderer.
+#[derive(Default)]
+pub struct CursorTextures(HashMap<(CursorIcon, usize), TextureBuffer<GlesTexture>>);
+
+/// Render the cursor described by `status` with its hotspot at `location`, for an output of `scale`.
+pub fn cursor_elements<R>(
+    renderer: &mut R,
+    theme: &mut CursorTheme,
+    textures: &mut CursorTextures,
+    status: &CursorImageStatus,
+    location: Point<f64, Logical>,
+    time: Duration,
+    scale: Scale<f64>,
+) -> Vec<CustomRenderElements<R>>
+where
+    R: Renderer + ImportAll + ImportMem + AsGlowRenderer,
+    <R as Renderer>::TextureId: 'static,
+{
+    match status {
+        CursorImageStatus::Hidden => vec![],
+        CursorImageStatus::Surface(surface) => {
+            let hotspot = with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<Mutex<CursorImageAttributes>>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .hotspot
+            });
+            render_elements_from_surface_tree(
+                renderer,
+                surface,
+                (location - hotspot.to_f64()).to_physical_precise_round(scale),
+                scale,
+                1.0,
+                Kind::Cursor,
+            )
+            .into_iter()
+            .map(CustomRenderElements::Surface)
+            .collect()
+        }
+        CursorImageStatus::Named(icon) => {
+            let (index, frame) = theme.frame(*icon, time);
+            let texture = match textures.0.entry((*icon, index)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    match TextureBuffer::from_memory(
+                        renderer.glow_renderer_mut(),
+                        &frame.pixels,
+                        Fourcc::Abgr8888,
+                        frame.size,
+                        false,
+                        frame.scale,
+                        Transform::Normal,
+                        None,
+                    ) {
+                        Ok(texture) => entry.insert(texture),
+                        Err(err) => {
+                            warn!("Failed to upload cursor texture: {}", err);
+                            return vec![];
+                        }
+                    }
+                }
+            };
+            let hotspot = Point::<f64, Logical>::from((
+                frame.hotspot.0 as f64 / frame.scale as f64,
+                frame.hotspot.1 as f64 / frame.scale as f64,
+            ));
+            vec![CustomRenderElements::from(
+                TextureRenderElement::from_texture_buffer(
+                    (location - hotspot).to_physical(scale),
+                    texture,
+                    None,
+                    None,
+                    None,
+                    Kind::Cursor,
+                ),
+            )]
+        }
+    }
+}
@@ -5,8 +5,9 @@ use smithay::{
         drm::DrmDeviceFd,
         renderer::{
             element::{
-                surface::WaylandSurfaceRenderElement, texture::TextureRenderElement, Element, Id,
-                RenderElement,
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+                texture::TextureRenderElement,
+                AsRenderElements, Element, Id, Kind, RenderElement,
             },
             gles::{element::PixelShaderElement, GlesFrame, GlesTexture, Uniform},
             glow::{GlowFrame, GlowRenderer},
@@ -15,14 +16,23 @@ use smithay::{
             ImportAll, ImportMem, Renderer, Texture,
         },
     },
-    utils::{Buffer, Physical, Rectangle, Scale},
+    desktop::Window,
+    output::Output,
+    reexports::wayland_server::Resource,
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Scale, Size},
 };
 
-use crate::state::CONFIG;
+use crate::{
+    handlers::session_lock::SessionLock,
+    state::{DndIcon, CONFIG},
+};
 
 use self::{border::BorderShader, corners::CornerShader};
+pub mod background;
 pub mod border;
 pub mod corners;
+pub mod cursor;
+pub mod title_bar;
 
 pub type GlMultiRenderer<'a> = MultiRenderer<
     'a,
@@ -254,6 +264,9 @@ where
     R: Renderer,
 {
     inner: WaylandSurfaceRenderElement<R>,
+    // scale of the output, the corner radius is given in logical pixels
+    scale: f64,
+    rounded: bool,
 }
 
 impl<R> Element for WindowRenderElement<R>
@@ -294,9 +307,24 @@ where
         self.inner.damage_since(scale, commit)
     }
 
-    fn opaque_regions(&self, _scale: Scale<f64>) -> Vec<Rectangle<i32, Physical>> {
-        // PERF: actually compute opaque regions
-        vec![]
+    fn opaque_regions(&self, scale: Scale<f64>) -> Vec<Rectangle<i32, Physical>> {
+        let regions = self.inner.opaque_regions(scale);
+        if !self.rounded {
+            return regions;
+        }
+        // the rounded corners are cut out of the surface
+        let size = self.geometry(scale).size;
+        let radius = (CONFIG.borders.radius as f64 * self.scale).ceil() as i32;
+        let corners = [
+            Rectangle::from_loc_and_size((0, 0), (radius, radius)),
+            Rectangle::from_loc_and_size((size.w - radius, 0), (radius, radius)),
+            Rectangle::from_loc_and_size((0, size.h - radius), (radius, radius)),
+            Rectangle::from_loc_and_size((size.w - radius, size.h - radius), (radius, radius)),
+        ];
+        regions
+            .into_iter()
+            .flat_map(|region| region.subtract_rects(corners))
+            .collect()
     }
 
     fn alpha(&self) -> f32 {
@@ -317,17 +345,14 @@ impl<'a> RenderElement<GlMultiRenderer<'a>> for WindowRenderElement<GlMultiRende
         damage: &[Rectangle<i32, Physical>],
     ) -> Result<(), <GlMultiRenderer<'a> as Renderer>::Error> {
         // apply shader to round corners (tty)
-        if CONFIG.borders.radius > 0.0 {
-            let size = self
-                .geometry(Scale::from(1.0))
-                .size
-                .to_logical(Scale::from(1));
+        if self.rounded {
+            let size = self.geometry(Scale::from(self.scale)).size;
             let framegl = <GlowFrame<'_> as BorrowMut<GlesFrame>>::borrow_mut(frame.as_mut());
             framegl.override_default_tex_program(
                 CornerShader::get(framegl.egl_context()),
                 vec![
                     Uniform::new("size", [size.w as f32, size.h as f32]),
-                    Uniform::new("radius", CONFIG.borders.radius),
+                    Uniform::new("radius", CONFIG.borders.radius * self.scale as f32),
                 ],
             );
             self.inner.draw(frame, src, dst, damage)?;
@@ -344,6 +369,10 @@ impl<'a> RenderElement<GlMultiRenderer<'a>> for WindowRenderElement<GlMultiRende
         &self,
         renderer: &mut GlMultiRenderer<'a>,
     ) -> Option<smithay::backend::renderer::element::UnderlyingStorage> {
+        // a plane would show the buffer without its rounded corners
+        if self.rounded {
+            return None;
+        }
         self.inner.underlying_storage(renderer)
     }
 }
@@ -357,17 +386,14 @@ impl RenderElement<GlowRenderer> for WindowRenderElement<GlowRenderer> {
         damage: &[Rectangle<i32, Physical>],
     ) -> Result<(), <GlowRenderer as Renderer>::Error> {
         // apply shader to round corners (winit)
-        if CONFIG.borders.radius > 0.0 {
-            let size = self
-                .geometry(Scale::from(1.0))
-                .size
-                .to_logical(Scale::from(1));
+        if self.rounded {
+            let size = self.geometry(Scale::from(self.scale)).size;
             let framegl = <GlowFrame<'_> as BorrowMut<GlesFrame>>::borrow_mut(frame);
             framegl.override_default_tex_program(
                 CornerShader::get(framegl.egl_context()),
                 vec![
                     Uniform::new("size", [size.w as f32, size.h as f32]),
-                    Uniform::new("radius", CONFIG.borders.radius),
+                    Uniform::new("radius", CONFIG.borders.radius * self.scale as f32),
                 ],
             );
             self.inner.draw(frame, src, dst, damage)?;
@@ -382,16 +408,24 @@ impl RenderElement<GlowRenderer> for WindowRenderElement<GlowRenderer> {
         &self,
         renderer: &mut GlowRenderer,
     ) -> Option<smithay::backend::renderer::element::UnderlyingStorage> {
+        if self.rounded {
+            return None;
+        }
         self.inner.underlying_storage(renderer)
     }
 }
 
-impl<R> From<WaylandSurfaceRenderElement<R>> for WindowRenderElement<R>
+impl<R> WindowRenderElement<R>
 where
     R: Renderer,
 {
-    fn from(value: WaylandSurfaceRenderElement<R>) -> Self {
-        WindowRenderElement { inner: value }
+    /// Wrap the main surface of a window, with its corners rounded if `rounded` and configured.
+    pub fn new(inner: WaylandSurfaceRenderElement<R>, scale: f64, rounded: bool) -> Self {
+        WindowRenderElement {
+            inner,
+            scale,
+            rounded: rounded && CONFIG.borders.radius > 0.0,
+        }
     }
 }
 
@@ -401,12 +435,14 @@ pub fn wrap_window_surface<
     C: From<WaylandSurfaceRenderElement<R>> + From<WindowRenderElement<R>>,
 >(
     mut elements: Vec<WaylandSurfaceRenderElement<R>>,
+    scale: f64,
+    rounded: bool,
 ) -> Vec<C>
 where
     <R as Renderer>::TextureId: Texture + 'static,
 {
     if let Some(elem) = elements.pop() {
-        let win = WindowRenderElement::from(elem);
+        let win = WindowRenderElement::new(elem, scale, rounded);
         let mut elements: Vec<C> = elements.into_iter().map(C::from).collect();
         elements.push(C::from(win));
         elements
@@ -415,6 +451,107 @@ where
     }
 }
 
+// unmanaged X11 windows are drawn undecorated where they placed themselves
+pub fn override_redirect_elements<R>(
+    windows: &[Window],
+    renderer: &mut R,
+    scale: Scale<f64>,
+) -> Vec<CustomRenderElements<R>>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    windows
+        .iter()
+        .rev()
+        .filter_map(|window| Some((window, window.x11_surface()?.geometry().loc)))
+        .flat_map(|(window, location)| {
+            AsRenderElements::<R>::render_elements::<WaylandSurfaceRenderElement<R>>(
+                window,
+                renderer,
+                location.to_physical_precise_round(scale),
+                scale,
+                1.0,
+            )
+        })
+        .map(CustomRenderElements::Surface)
+        .collect()
+}
+
+// the icon of an ongoing drag follows the pointer, moved by the offsets of its buffers
+pub fn dnd_icon_elements<R>(
+    icon: Option<&DndIcon>,
+    location: Point<f64, Logical>,
+    renderer: &mut R,
+    scale: Scale<f64>,
+) -> Vec<CustomRenderElements<R>>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    icon.filter(|icon| icon.surface.is_alive())
+        .map(|icon| {
+            render_elements_from_surface_tree(
+                renderer,
+                &icon.surface,
+                (location + icon.offset.to_f64())
+                    .to_physical(scale)
+                    .to_i32_round(),
+                scale,
+                1.0,
+                Kind::Unspecified,
+            )
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .map(CustomRenderElements::Surface)
+        .collect()
+}
+
+/// Size of `output` in logical coordinates, after its transform and scale.
+pub fn output_size(output: &Output) -> Size<i32, Logical> {
+    let mode_size = output
+        .current_mode()
+        .map(|mode| mode.size)
+        .unwrap_or_default();
+    output
+        .current_transform()
+        .transform_size(mode_size)
+        .to_f64()
+        .to_logical(output.current_scale().fractional_scale())
+        .to_i32_ceil()
+}
+
+/// Colour of locked outputs, visible where the locker has no surface (e.g. after it crashed)
+pub const LOCKED_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+// the lock surface of `output` replaces everything but the cursor
+pub fn lock_elements<R>(
+    lock: &SessionLock,
+    output: &Output,
+    renderer: &mut R,
+) -> Vec<CustomRenderElements<R>>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: 'static,
+{
+    lock.surface(output)
+        .map(|surface| {
+            render_elements_from_surface_tree(
+                renderer,
+                surface.wl_surface(),
+                (0, 0),
+                output.current_scale().fractional_scale(),
+                1.0,
+                Kind::Unspecified,
+            )
+        })
+        .unwrap_or_default()
+        .into_iter()
+        .map(CustomRenderElements::Surface)
+        .collect()
+}
+
 pub fn init_shaders(renderer: &mut GlowRenderer) {
     BorderShader::init(renderer);
     CornerShader::init(renderer);
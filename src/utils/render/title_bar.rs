@@ -0,0 +1,79 @@
+use smithay::desktop::Window;
+#[cfg(feature = "title_bars")]
+use smithay::{
+    backend::renderer::{
+        element::texture::TextureRenderElement,
+        gles::{GlesError, GlesTexture},
+        glow::GlowRenderer,
+    },
+    utils::{Logical, Rectangle},
+};
+#[cfg(feature = "title_bars")]
+use std::cell::RefCell;
+
+use crate::{state::CONFIG, utils::window::WindowExt};
+
+/// Height of the title bar of `window`, 0 if it has none.
+pub fn height(window: &Window) -> i32 {
+    if cfg!(feature = "title_bars") && CONFIG.title_bars.height > 0 && window.is_server_decorated()
+    {
+        CONFIG.title_bars.height as i32
+    } else {
+        0
+    }
+}
+
+#[cfg(feature = "title_bars")]
+struct TitleBarState(RefCell<smithay_egui::EguiState>);
+
+/// Render the title bar of `window` into `area`, in global coordinates.
+#[cfg(feature = "title_bars")]
+pub fn element(
+    renderer: &mut GlowRenderer,
+    window: &Window,
+    area: Rectangle<i32, Logical>,
+    scale: f64,
+) -> Result<TextureRenderElement<GlesTexture>, GlesError> {
+    window.user_data().insert_if_missing(|| {
+        TitleBarState(RefCell::new(smithay_egui::EguiState::new(
+            Rectangle::from_loc_and_size((0, 0), area.size),
+        )))
+    });
+    let state = window.user_data().get::<TitleBarState>().unwrap();
+    let title = window.title();
+    let color = to_color32(CONFIG.title_bars.color);
+    let text_color = to_color32(CONFIG.title_bars.text_color);
+    state.0.borrow_mut().render(
+        |ctx| {
+            egui::Area::new("title_bar")
+                .fixed_pos((0.0, 0.0))
+                .show(ctx, |ui| {
+                    egui::Frame::none()
+                        .fill(color)
+                        .inner_margin(egui::Margin::symmetric(6.0, 0.0))
+                        .show(ui, |ui| {
+                            ui.set_min_size(egui::vec2(area.size.w as f32, area.size.h as f32));
+                            ui.set_max_size(egui::vec2(area.size.w as f32, area.size.h as f32));
+                            ui.centered_and_justified(|ui| {
+                                ui.add(egui::Label::new(
+                                    egui::RichText::new(title).color(text_color),
+                                ))
+                            });
+                        });
+                });
+        },
+        renderer,
+        area,
+        scale,
+        1.0,
+    )
+}
+
+#[cfg(feature = "title_bars")]
+fn to_color32(rgb: [f32; 3]) -> egui::Color32 {
+    egui::Color32::from_rgb(
+        (rgb[0] * 255.0) as u8,
+        (rgb[1] * 255.0) as u8,
+        (rgb[2] * 255.0) as u8,
+    )
+}
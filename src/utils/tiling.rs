@@ -3,12 +3,14 @@ use std::{cell::RefCell, rc::Rc};
 use crate::state::CONFIG;
 use smithay::{
     desktop::layer_map_for_output,
-    utils::{Logical, Physical, Point, Rectangle, Size},
+    utils::{Logical, Point, Rectangle, Size},
 };
 use tracing::debug;
 
 use super::{
     binarytree::{BinaryTree, HorizontalOrVertical},
+    render::output_size,
+    window::WindowExt,
     workspace::{MagmaWindow, Workspace},
 };
 
@@ -16,14 +18,13 @@ pub fn bsp_update_layout(workspace: &mut Workspace) {
     let gaps = CONFIG.gaps;
     //recalculate the size and location of the windows
 
-    let output = layer_map_for_output(workspace.outputs().next().unwrap()).non_exclusive_zone();
-    let output_full = workspace
-        .outputs()
-        .next()
-        .unwrap()
-        .current_mode()
-        .unwrap()
-        .size;
+    // nothing to lay out on until an output is connected
+    let output = match workspace.outputs().next() {
+        Some(output) => output,
+        None => return,
+    };
+    let output_full = output_size(output);
+    let output = layer_map_for_output(output).non_exclusive_zone();
 
     match &mut workspace.layout_tree {
         BinaryTree::Empty => {}
@@ -66,11 +67,7 @@ pub fn bsp_update_layout(workspace: &mut Workspace) {
     }
     debug!("{:#?}", workspace.layout_tree);
     for magmawindow in workspace.magmawindows() {
-        let xdg_toplevel = magmawindow.window.toplevel();
-        xdg_toplevel.unwrap().with_pending_state(|state| {
-            state.size = Some(magmawindow.rec.size);
-        });
-        xdg_toplevel.unwrap().send_configure();
+        magmawindow.window.configure(magmawindow.client_rec());
     }
 }
 
@@ -80,7 +77,7 @@ pub fn generate_layout(
     lastgeo: Rectangle<i32, Logical>,
     split: HorizontalOrVertical,
     ratio: f32,
-    output: Size<i32, Physical>,
+    output: Size<i32, Logical>,
     gaps: (i32, i32),
 ) {
     let size = match split {
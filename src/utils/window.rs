@@ -0,0 +1,115 @@
+use std::sync::Mutex;
+
+use smithay::{
+    desktop::{Window, WindowSurface},
+    reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode,
+    utils::{Logical, Rectangle},
+    wayland::{compositor::with_states, shell::xdg::XdgToplevelSurfaceRoleAttributes},
+};
+use tracing::warn;
+
+use crate::{config::Decoration, state::CONFIG};
+
+/// Operations on windows that differ between Wayland and X11 clients.
+pub trait WindowExt {
+    /// Place the window into `rec`, as decided by the layout.
+    fn configure(&self, rec: Rectangle<i32, Logical>);
+    /// Send pending state changes, like activation, to the client.
+    fn send_configure(&self);
+    /// Ask the client to close the window.
+    fn close(&self);
+    /// Title of the window, empty if not set.
+    fn title(&self) -> String;
+    /// App id of the window, the class for X11 windows, empty if not set.
+    fn app_id(&self) -> String;
+    /// Whether the compositor draws the decorations of the window.
+    fn is_server_decorated(&self) -> bool;
+}
+
+impl WindowExt for Window {
+    fn configure(&self, rec: Rectangle<i32, Logical>) {
+        match self.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.with_pending_state(|state| {
+                    state.size = Some(rec.size);
+                });
+                toplevel.send_configure();
+            }
+            WindowSurface::X11(surface) => {
+                if let Err(err) = surface.configure(rec) {
+                    warn!("Failed to configure X11 window: {}", err);
+                }
+            }
+        }
+    }
+
+    fn send_configure(&self) {
+        match self.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.send_configure();
+            }
+            // X11 windows are configured immediately
+            WindowSurface::X11(_) => {}
+        }
+    }
+
+    fn close(&self) {
+        match self.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => toplevel.send_close(),
+            WindowSurface::X11(surface) => {
+                if let Err(err) = surface.close() {
+                    warn!("Failed to close X11 window: {}", err);
+                }
+            }
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => with_states(toplevel.wl_surface(), |states| {
+                states
+                    .data_map
+                    .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .title
+                    .clone()
+                    .unwrap_or_default()
+            }),
+            WindowSurface::X11(surface) => surface.title(),
+        }
+    }
+
+    fn app_id(&self) -> String {
+        match self.underlying_surface() {
+            WindowSurface::Wayland(toplevel) => with_states(toplevel.wl_surface(), |states| {
+                states
+                    .data_map
+                    .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .app_id
+                    .clone()
+                    .unwrap_or_default()
+            }),
+            WindowSurface::X11(surface) => surface.class(),
+        }
+    }
+
+    fn is_server_decorated(&self) -> bool {
+        match self.underlying_surface() {
+            // the pending mode, so the layout matches the next configure
+            WindowSurface::Wayland(toplevel) => {
+                toplevel.with_pending_state(|state| state.decoration_mode) == Some(Mode::ServerSide)
+            }
+            WindowSurface::X11(surface) => {
+                match CONFIG.decoration(&surface.class(), &surface.title()) {
+                    Some(decoration) => decoration == Decoration::Server,
+                    None => !surface.is_decorated(),
+                }
+            }
+        }
+    }
+}
@@ -1,24 +1,33 @@
 use std::{
     cell::{Ref, RefCell},
+    collections::HashMap,
     rc::Rc,
 };
 
 use smithay::{
     backend::renderer::{
-        element::{surface::WaylandSurfaceRenderElement, AsRenderElements},
-        gles::element::PixelShaderElement,
+        element::{
+            surface::WaylandSurfaceRenderElement, texture::TextureRenderElement, AsRenderElements,
+        },
+        gles::{element::PixelShaderElement, GlesTexture},
         ImportAll, Renderer, Texture,
     },
     desktop::{space::SpaceElement, Window},
     output::Output,
-    utils::{Logical, Point, Rectangle, Scale, Transform},
+    utils::{Logical, Point, Rectangle, Scale},
 };
 
+#[cfg(feature = "title_bars")]
+use tracing::warn;
+
 use crate::state::CONFIG;
 
 use super::{
     binarytree::BinaryTree,
-    render::{border::BorderShader, wrap_window_surface, AsGlowRenderer, WindowRenderElement},
+    render::{
+        border::BorderShader, output_size, title_bar, wrap_window_surface, AsGlowRenderer,
+        WindowRenderElement,
+    },
     tiling::bsp_update_layout,
 };
 
@@ -26,16 +35,27 @@ use super::{
 pub struct MagmaWindow {
     pub window: Window,
     pub rec: Rectangle<i32, Logical>,
+    /// Set when the window demands attention, cleared once it is focused.
+    pub urgent: bool,
 }
 impl MagmaWindow {
+    /// The part of `rec` left to the client, below its title bar.
+    pub fn client_rec(&self) -> Rectangle<i32, Logical> {
+        let title_bar = title_bar::height(&self.window);
+        Rectangle::from_loc_and_size(
+            (self.rec.loc.x, self.rec.loc.y + title_bar),
+            (self.rec.size.w, self.rec.size.h - title_bar),
+        )
+    }
+
     fn bbox(&self) -> Rectangle<i32, Logical> {
         let mut bbox = self.window.bbox();
-        bbox.loc += self.rec.loc - self.window.geometry().loc;
+        bbox.loc += self.client_rec().loc - self.window.geometry().loc;
         bbox
     }
 
     fn render_location(&self) -> Point<i32, Logical> {
-        self.rec.loc - self.window.geometry().loc
+        self.client_rec().loc - self.window.geometry().loc
     }
 }
 pub struct Workspace {
@@ -92,31 +112,65 @@ impl Workspace {
         R: Renderer + ImportAll + AsGlowRenderer,
         C: From<WaylandSurfaceRenderElement<R>>
             + From<PixelShaderElement>
+            + From<TextureRenderElement<GlesTexture>>
             + From<WindowRenderElement<R>>,
     >(
         &self,
         renderer: &mut R,
+        output: &Output,
+        scale: Scale<f64>,
     ) -> Vec<C>
     where
         <R as Renderer>::TextureId: Clone + Texture + 'static,
     {
         let mut render_elements: Vec<C> = Vec::new();
+        let fullscreen = self.fullscreen_window(output).map(|w| w.clone());
         for element in &self.windows {
             let window = &element.borrow().window;
-            if CONFIG.borders.thickness > 0 {
+            // nothing is drawn around a window covering the output, so its buffer can be
+            // scanned out directly
+            let decorated = fullscreen.as_ref() != Some(window);
+            let title_bar = title_bar::height(window);
+            // the window as drawn, including its title bar
+            let area = Rectangle::from_loc_and_size(
+                element.borrow().rec.loc,
+                window.geometry().size + (0, title_bar).into(),
+            );
+            if decorated && CONFIG.borders.thickness > 0 {
                 render_elements.push(C::from(BorderShader::element(
                     renderer.glow_renderer_mut(),
                     window,
-                    element.borrow().rec.loc,
+                    area,
+                    element.borrow().urgent,
+                    scale.x,
                 )));
             }
+            #[cfg(feature = "title_bars")]
+            if decorated && title_bar > 0 {
+                match title_bar::element(
+                    renderer.glow_renderer_mut(),
+                    window,
+                    Rectangle::from_loc_and_size(area.loc, (area.size.w, title_bar)),
+                    scale.x,
+                ) {
+                    Ok(elem) => render_elements.push(C::from(elem)),
+                    Err(err) => warn!("Failed to render title bar: {:?}", err),
+                }
+            }
             //wrap and add window surfaces
-            render_elements.append(&mut wrap_window_surface(window.render_elements(
-                renderer,
-                element.borrow().render_location().to_physical(1),
-                Scale::from(1.0),
-                1.0,
-            )));
+            render_elements.append(&mut wrap_window_surface(
+                window.render_elements(
+                    renderer,
+                    element
+                        .borrow()
+                        .render_location()
+                        .to_physical_precise_round(scale),
+                    scale,
+                    1.0,
+                ),
+                scale.x,
+                decorated,
+            ));
         }
         render_elements
     }
@@ -129,6 +183,10 @@ impl Workspace {
         self.outputs.push(output);
     }
 
+    pub fn insert_output(&mut self, index: usize, output: Output) {
+        self.outputs.insert(index.min(self.outputs.len()), output);
+    }
+
     pub fn remove_output(&mut self, output: &Output) {
         self.outputs.retain(|o| o != output);
     }
@@ -138,17 +196,8 @@ impl Workspace {
             return None;
         }
 
-        let transform: Transform = o.current_transform();
-        o.current_mode().map(|mode| {
-            Rectangle::from_loc_and_size(
-                (0, 0),
-                transform
-                    .transform_size(mode.size)
-                    .to_f64()
-                    .to_logical(o.current_scale().fractional_scale())
-                    .to_i32_ceil(),
-            )
-        })
+        o.current_mode()
+            .map(|_| Rectangle::from_loc_and_size((0, 0), output_size(o)))
     }
 
     pub fn window_under<P: Into<Point<f64, Logical>>>(
@@ -176,6 +225,19 @@ impl Workspace {
     pub fn contains_window(&self, window: &Window) -> bool {
         self.windows.iter().any(|w| &w.borrow().window == window)
     }
+
+    /// The window covering all of `output`, if any.
+    pub fn fullscreen_window(&self, output: &Output) -> Option<Ref<'_, Window>> {
+        let output_geometry = self.output_geometry(output)?;
+        self.windows
+            .iter()
+            .find(|w| {
+                let w = w.borrow();
+                Rectangle::from_loc_and_size(w.client_rec().loc, w.window.geometry().size)
+                    .contains_rect(output_geometry)
+            })
+            .map(|w| Ref::map(w.borrow(), |hw| &hw.window))
+    }
 }
 
 impl Default for Workspace {
@@ -187,6 +249,9 @@ impl Default for Workspace {
 pub struct Workspaces {
     workspaces: Vec<Workspace>,
     pub current: u8,
+    // positions of unplugged outputs in every workspace, keyed by monitor identity and
+    // connector name, as identical monitors can share the former
+    disconnected_outputs: HashMap<(String, String), Vec<Option<usize>>>,
 }
 
 impl Workspaces {
@@ -194,13 +259,68 @@ impl Workspaces {
         Workspaces {
             workspaces: (0..workspaceamount).map(|_| Workspace::new()).collect(),
             current: 0,
+            disconnected_outputs: HashMap::new(),
         }
     }
 
+    /// Map a newly connected output to every workspace.
+    ///
+    /// If an output with the same `id` was disconnected before it takes its old place again,
+    /// moving the windows that were migrated away from it back. Among several of them the one
+    /// unplugged from the same connector is preferred.
+    pub fn connect_output(&mut self, output: &Output, id: &str) {
+        let key = (id.to_string(), output.name());
+        let key = if self.disconnected_outputs.contains_key(&key) {
+            Some(key)
+        } else {
+            self.disconnected_outputs
+                .keys()
+                .find(|(other, _)| other == id)
+                .cloned()
+        };
+        let positions = key
+            .and_then(|key| self.disconnected_outputs.remove(&key))
+            .unwrap_or_default();
+        for (i, workspace) in self.workspaces.iter_mut().enumerate() {
+            match positions.get(i).copied().flatten() {
+                Some(position) => workspace.insert_output(position, output.clone()),
+                None => workspace.add_output(output.clone()),
+            }
+            bsp_update_layout(workspace);
+        }
+    }
+
+    /// Remove an unplugged output from every workspace and lay its windows out on the remaining outputs.
+    pub fn disconnect_output(&mut self, output: &Output, id: String) {
+        let positions = self
+            .workspaces
+            .iter_mut()
+            .map(|workspace| {
+                let position = workspace.outputs().position(|o| o == output);
+                workspace.remove_output(output);
+                bsp_update_layout(workspace);
+                position
+            })
+            .collect();
+        self.disconnected_outputs
+            .insert((id, output.name()), positions);
+    }
+
     pub fn outputs(&self) -> impl Iterator<Item = &Output> {
         self.workspaces.iter().flat_map(|w| w.outputs())
     }
 
+    /// Lay out the workspaces tiled on `output` again, e.g. after its exclusive zone changed.
+    pub fn update_output_layout(&mut self, output: &Output) {
+        for workspace in self
+            .workspaces
+            .iter_mut()
+            .filter(|w| w.outputs().next() == Some(output))
+        {
+            bsp_update_layout(workspace);
+        }
+    }
+
     pub fn iter(&mut self) -> impl Iterator<Item = &mut Workspace> {
         self.workspaces.iter_mut()
     }
@@ -223,6 +343,40 @@ impl Workspaces {
             .find(|w| w.contains_window(window))
     }
 
+    /// Mark `window` as demanding attention or not.
+    pub fn set_urgent(&mut self, window: &Window, urgent: bool) {
+        for element in self.workspaces.iter().flat_map(|w| &w.windows) {
+            if &element.borrow().window == window {
+                element.borrow_mut().urgent = urgent;
+            }
+        }
+    }
+
+    /// Whether `window` is demanding attention.
+    pub fn is_urgent(&self, window: &Window) -> bool {
+        self.workspaces
+            .iter()
+            .flat_map(|w| w.magmawindows())
+            .any(|w| &w.window == window && w.urgent)
+    }
+
+    /// A window demanding attention, preferring the current workspace.
+    pub fn urgent_window(&self) -> Option<Window> {
+        self.current()
+            .magmawindows()
+            .chain(self.workspaces.iter().flat_map(|w| w.magmawindows()))
+            .find(|w| w.urgent)
+            .map(|w| w.window.clone())
+    }
+
+    /// Index of the workspace `window` is on.
+    pub fn workspace_id_from_window(&self, window: &Window) -> Option<u8> {
+        self.workspaces
+            .iter()
+            .position(|w| w.contains_window(window))
+            .map(|id| id as u8)
+    }
+
     pub fn activate(&mut self, id: u8) {
         self.current = id;
     }